@@ -0,0 +1,145 @@
+// Named bookmarks and comments attached to program memory addresses, e.g. to
+// document what the blocks of a program without source do
+
+use std::collections::BTreeMap;
+use std::ops::Bound;
+
+#[derive(Default, Clone, Debug, PartialEq, Eq)]
+pub struct Annotation {
+    pub bookmark: Option<String>,
+    pub comment: Option<String>,
+}
+
+impl Annotation {
+    // E.g. `[init] clear the buffer`
+    pub fn describe(&self) -> String {
+        match (&self.bookmark, &self.comment) {
+            (Some(name), Some(comment)) => format!("[{}] {}", name, comment),
+            (Some(name), None) => format!("[{}]", name),
+            (None, Some(comment)) => comment.clone(),
+            (None, None) => String::new(),
+        }
+    }
+}
+
+#[derive(Default)]
+pub struct Annotations {
+    entries: BTreeMap<u16, Annotation>,
+}
+
+// Bookmarks are referred to by name, so they are single words
+pub fn check_bookmark_name(name: &str) -> Result<(), String> {
+    match name {
+        "" => Err(String::from("Missing bookmark name")),
+        _ if name.contains(char::is_whitespace) => {
+            Err(format!("Bookmark names cannot contain spaces: '{}'", name))
+        }
+        _ => Ok(()),
+    }
+}
+
+impl Annotations {
+    pub fn get(&self, address: u16) -> Option<&Annotation> {
+        self.entries.get(&address)
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = (u16, &Annotation)> {
+        self.entries.iter().map(|(address, a)| (*address, a))
+    }
+
+    // Entries without bookmark and comment are dropped
+    fn update(&mut self, address: u16, change: impl FnOnce(&mut Annotation)) {
+        let annotation = self.entries.entry(address).or_default();
+        change(annotation);
+        if *annotation == Annotation::default() {
+            self.entries.remove(&address);
+        }
+    }
+
+    // Set or remove (None) the bookmark at the address. A name can only be used
+    // once, an existing bookmark of the same name is moved.
+    pub fn set_bookmark(&mut self, address: u16, name: Option<String>) {
+        if let Some(old_address) = name.as_ref().and_then(|name| self.find_bookmark(name)) {
+            self.update(old_address, |a| a.bookmark = None);
+        }
+        self.update(address, |a| a.bookmark = name);
+    }
+
+    pub fn set_comment(&mut self, address: u16, text: Option<String>) {
+        self.update(address, |a| a.comment = text);
+    }
+
+    pub fn find_bookmark(&self, name: &str) -> Option<u16> {
+        self.iter()
+            .find(|(_, a)| a.bookmark.as_deref() == Some(name))
+            .map(|(address, _)| address)
+    }
+
+    // The closest annotated address after (or before) the address, wrapping
+    // around at the ends
+    pub fn next(&self, from: u16, forward: bool) -> Option<u16> {
+        match forward {
+            true => self
+                .entries
+                .range((Bound::Excluded(from), Bound::Unbounded))
+                .next()
+                .or(self.entries.iter().next()),
+            false => self
+                .entries
+                .range(..from)
+                .next_back()
+                .or(self.entries.iter().next_back()),
+        }
+        .map(|(address, _)| *address)
+    }
+
+    // Move every annotation to a new address, e.g. after the program was reloaded.
+    // Annotations ending up at the same address are merged.
+    pub fn relocate(&mut self, new_address: impl Fn(u16) -> u16) {
+        for (address, annotation) in std::mem::take(&mut self.entries) {
+            let address = new_address(address);
+            let entry = self.entries.entry(address).or_default();
+            entry.bookmark = annotation.bookmark.or(entry.bookmark.take());
+            entry.comment = annotation.comment.or(entry.comment.take());
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn annotations_test() {
+        let mut annotations = Annotations::default();
+        annotations.set_bookmark(0x10, Some(String::from("init")));
+        annotations.set_comment(0x10, Some(String::from("clear the buffer")));
+        annotations.set_comment(0x4, Some(String::from("read a key")));
+        annotations.set_bookmark(0x20, Some(String::from("print")));
+
+        assert_eq!(annotations.find_bookmark("print"), Some(0x20));
+        assert_eq!(annotations.next(0x4, true), Some(0x10));
+        assert_eq!(annotations.next(0x20, true), Some(0x4));
+        assert_eq!(annotations.next(0x10, false), Some(0x4));
+        assert_eq!(annotations.next(0x0, false), Some(0x20));
+
+        // Names are unique, setting one again moves it
+        annotations.set_bookmark(0x4, Some(String::from("print")));
+        assert_eq!(annotations.find_bookmark("print"), Some(0x4));
+        assert!(annotations.get(0x20).is_none());
+
+        annotations.set_comment(0x10, None);
+        assert_eq!(
+            annotations.get(0x10).and_then(|a| a.bookmark.as_deref()),
+            Some("init")
+        );
+        annotations.set_bookmark(0x10, None);
+        assert!(annotations.get(0x10).is_none());
+
+        annotations.relocate(|address| address + 2);
+        assert_eq!(annotations.find_bookmark("print"), Some(0x6));
+
+        assert!(check_bookmark_name("main loop").is_err());
+        assert!(check_bookmark_name("").is_err());
+    }
+}
@@ -0,0 +1,402 @@
+// Assembler for masm syntax, e.g. `add %reg0 %reg1 %reg2`, `ldc %reg2 0x8000`
+// or `jnzr loop`. Operands may be separated by commas, labels are defined as
+// `name:` and comments start with '#'.
+//
+// Note that offsets of relative jumps are written relative to the jump itself
+// (`jcr 0` jumps to itself), while the encoded offset is one less since the
+// program counter is incremented after the jump.
+
+use super::decoder::InstructionWord;
+use super::expression::parse_number;
+use super::ir::*;
+use super::program::Program;
+use super::source_map::SourceMap;
+use super::symbols::{is_valid_name, SymbolTable};
+
+fn parse_register(text: &str) -> Result<Register, String> {
+    match text.strip_prefix("%reg").map(str::parse::<usize>) {
+        Some(Ok(reg)) if reg < 8 => Ok(reg),
+        _ => Err(format!("Invalid register '{}'", text)),
+    }
+}
+
+// Signed or unsigned number, e.g. -0x1, 42, 0xffff
+fn parse_signed(text: &str) -> Result<i32, String> {
+    match text.strip_prefix('-') {
+        Some(digits) => Ok(-(parse_number::<u16>(digits)? as i32)),
+        None => Ok(parse_number::<u16>(text)? as i32),
+    }
+}
+
+// Number or label, as operand of ldc
+fn parse_constant(text: &str, symbols: &SymbolTable) -> Result<u16, String> {
+    if let Some(address) = symbols.address_of(text) {
+        return Ok(address);
+    }
+
+    match parse_signed(text)? {
+        value @ -0x8000..=0xFFFF => Ok(value as u16),
+        _ => Err(format!("Constant '{}' does not fit into 16 bits", text)),
+    }
+}
+
+// Convert a jump offset relative to the jump instruction into its encoded form
+pub fn encode_jump_offset(offset: i32) -> Result<i16, String> {
+    match offset - 1 {
+        encoded @ -0x800..=0x7FF => Ok(encoded as i16),
+        _ => Err(format!("Jump offset {} is out of range", offset)),
+    }
+}
+
+fn expect_operands(operands: &[&str], n: usize) -> Result<(), String> {
+    match operands.len() == n {
+        true => Ok(()),
+        false => Err(format!("Expected {} operand(s), got {}", n, operands.len())),
+    }
+}
+
+// Parse an instruction without labels
+pub fn parse_instruction(text: &str) -> Result<Operation, String> {
+    parse_instruction_at(text, 0, &SymbolTable::default())
+}
+
+// Parse an instruction located at the given address. Relative jumps take either
+// an offset or a label, ldc takes a number or a label.
+pub fn parse_instruction_at(
+    text: &str,
+    address: u16,
+    symbols: &SymbolTable,
+) -> Result<Operation, String> {
+    let resolve_jump = |operand: &str| match symbols.address_of(operand) {
+        Some(target) => Ok(target as i32 - address as i32),
+        None if is_valid_name(operand) => Err(format!("Unknown label '{}'", operand)),
+        None => parse_signed(operand),
+    };
+
+    let text = text.replace(',', " ");
+    let mut words = text.split_whitespace();
+    let mnemonic = words
+        .next()
+        .ok_or(String::from("Missing instruction"))?
+        .to_lowercase();
+    let operands: Vec<&str> = words.collect();
+
+    let unary = |target: usize, source: usize| -> Result<UnaryOp, String> {
+        expect_operands(&operands, 2)?;
+        Ok(UnaryOp {
+            target: parse_register(operands[target])?,
+            source_a: parse_register(operands[source])?,
+        })
+    };
+    // inc and dec modify their single operand in place
+    let in_place = || -> Result<UnaryOp, String> {
+        expect_operands(&operands, 1)?;
+        Ok(UnaryOp {
+            target: 0,
+            source_a: parse_register(operands[0])?,
+        })
+    };
+    let binary = || -> Result<BinaryOp, String> {
+        expect_operands(&operands, 3)?;
+        Ok(BinaryOp {
+            target: parse_register(operands[0])?,
+            source_a: parse_register(operands[1])?,
+            source_b: parse_register(operands[2])?,
+        })
+    };
+    let absolute_jump = |condition| -> Result<Operation, String> {
+        expect_operands(&operands, 1)?;
+        Ok(Operation::Jump {
+            target: JumpTarget::AbsoluteAdressRegister(parse_register(operands[0])?),
+            condition,
+        })
+    };
+    let relative_jump = |condition| -> Result<Operation, String> {
+        expect_operands(&operands, 1)?;
+        let offset = encode_jump_offset(resolve_jump(operands[0])?)?;
+        Ok(Operation::Jump {
+            target: JumpTarget::AddressOffsetConstant(offset),
+            condition,
+        })
+    };
+    let no_operands = |op: Operation| -> Result<Operation, String> {
+        expect_operands(&operands, 0)?;
+        Ok(op)
+    };
+
+    match mnemonic.as_str() {
+        "add" => Ok(Operation::Add(binary()?)),
+        "addc" | "adc" => Ok(Operation::AddCarry(binary()?)),
+        "add3" => {
+            expect_operands(&operands, 4)?;
+            Ok(Operation::Add3(TernaryOp {
+                target: parse_register(operands[0])?,
+                source_a: parse_register(operands[1])?,
+                source_b: parse_register(operands[2])?,
+                source_c: parse_register(operands[3])?,
+            }))
+        }
+        "sub" => Ok(Operation::Sub(binary()?)),
+        "subc" => Ok(Operation::SubCarry(binary()?)),
+        "mul" => Ok(Operation::Multiply(binary()?)),
+        "and" => Ok(Operation::And(binary()?)),
+        "or" => Ok(Operation::Or(binary()?)),
+        "xor" => Ok(Operation::Xor(binary()?)),
+        "xnor" => Ok(Operation::Xnor(binary()?)),
+        "shl" => Ok(Operation::ShiftLeft(binary()?)),
+        "shr" => Ok(Operation::ShiftRight(binary()?)),
+        "tst" => {
+            expect_operands(&operands, 2)?;
+            Ok(Operation::Test(BinaryOp {
+                target: 0,
+                source_a: parse_register(operands[0])?,
+                source_b: parse_register(operands[1])?,
+            }))
+        }
+        "inc" => Ok(Operation::Inc(in_place()?)),
+        "dec" => Ok(Operation::Dec(in_place()?)),
+        "not" => Ok(Operation::Not(unary(0, 1)?)),
+        "neg" => Ok(Operation::Neg(unary(0, 1)?)),
+        "mov" => Ok(Operation::Move(unary(0, 1)?)),
+
+        "jmp" => absolute_jump(JumpCondition::Always),
+        "jz" => absolute_jump(JumpCondition::Zero),
+        "jnz" => absolute_jump(JumpCondition::NotZero),
+        "jc" => absolute_jump(JumpCondition::Carry),
+        "jnc" => absolute_jump(JumpCondition::NotCarry),
+        "jrcon" | "jr" => relative_jump(JumpCondition::Always),
+        "jzr" => relative_jump(JumpCondition::Zero),
+        "jnzr" => relative_jump(JumpCondition::NotZero),
+        "jcr" => relative_jump(JumpCondition::Carry),
+        "jncr" => relative_jump(JumpCondition::NotCarry),
+
+        "st" => {
+            expect_operands(&operands, 2)?;
+            Ok(Operation::Store {
+                address_register: parse_register(operands[0])?,
+                data_register: parse_register(operands[1])?,
+            })
+        }
+        "ld" => {
+            expect_operands(&operands, 2)?;
+            Ok(Operation::Load {
+                target_register: parse_register(operands[0])?,
+                source: LoadSource::RAM {
+                    address_register: parse_register(operands[1])?,
+                },
+            })
+        }
+        "ldc" => {
+            expect_operands(&operands, 2)?;
+            Ok(Operation::Load {
+                target_register: parse_register(operands[0])?,
+                source: LoadSource::Constant(parse_constant(operands[1], symbols)?),
+            })
+        }
+
+        "nop" => no_operands(Operation::Noop),
+        "dbg" => no_operands(Operation::Debug),
+        "hlt" => no_operands(Operation::Halt),
+
+        _ => Err(format!("Unknown instruction '{}'", mnemonic)),
+    }
+}
+
+// Parse either a raw instruction word of exactly 5 hex digits or an instruction
+pub fn parse_word(text: &str) -> Result<InstructionWord, String> {
+    parse_word_at(text, 0, &SymbolTable::default())
+}
+
+pub fn parse_word_at(
+    text: &str,
+    address: u16,
+    symbols: &SymbolTable,
+) -> Result<InstructionWord, String> {
+    let text = text.trim();
+    if text.len() == 5 && text.chars().all(|ch| ch.is_ascii_hexdigit()) {
+        return Ok(InstructionWord::from(
+            u32::from_str_radix(text, 16).map_err(|err| err.to_string())?,
+        ));
+    }
+
+    Ok(InstructionWord::from(&parse_instruction_at(
+        text, address, symbols,
+    )?))
+}
+
+// Split a source line into its labels and the instruction, if any
+fn split_line(line: &str) -> (Vec<&str>, &str) {
+    let mut rest = line.split('#').next().unwrap_or("").trim();
+    let mut labels = Vec::new();
+
+    while let Some((label, tail)) = rest.split_once(':') {
+        labels.push(label.trim());
+        rest = tail.trim();
+    }
+
+    (labels, rest)
+}
+
+// Assemble a whole source file. Labels are collected in a first pass, so jumps
+// may refer to labels defined further down.
+pub fn assemble_source(text: &str) -> Result<Program, String> {
+    let mut symbols = SymbolTable::default();
+    let mut instructions = Vec::new();
+
+    for (index, line) in text.lines().enumerate() {
+        let (labels, instruction) = split_line(line);
+        let address = instructions.len() as u16;
+
+        for label in labels {
+            symbols
+                .insert(label, address)
+                .map_err(|msg| format!("line {}: {}", index + 1, msg))?;
+        }
+        if !instruction.is_empty() {
+            instructions.push((index + 1, instruction));
+        }
+    }
+
+    let mut words = Vec::new();
+    for (address, (line, instruction)) in instructions.iter().enumerate() {
+        let op = parse_instruction_at(instruction, address as u16, &symbols)
+            .map_err(|msg| format!("line {}: {}", line, msg))?;
+        words.push(InstructionWord::from(&op).buffer);
+    }
+
+    let mut program = Program::from(words.as_slice());
+    program.symbols = symbols;
+    program.source = Some(SourceMap::new(
+        text,
+        instructions.iter().map(|(line, _)| line - 1).collect(),
+    ));
+    Ok(program)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn assemble_test() {
+        // Words taken from masm output
+        let cases = [
+            ("ldc %reg2 0x8001", 0x800a1),
+            ("inc %reg0", 0x00005),
+            ("add %reg0 %reg1 %reg2", 0x01100),
+            ("add3 %reg3 %reg4 %reg5 %reg2", 0x6ac01),
+            ("subc %reg2, %reg1, %reg4", 0x42104),
+            ("ldc %reg1 91", 0x0059b),
+            ("nop", 0x0006c),
+            ("JZ %reg2", 0x00251),
+            ("jcr 0", 0xfff5b),
+            ("jcr 5", 0x0045b),
+            ("jrcon -2", 0xffd58),
+            ("st %reg3 %reg1", 0x01968),
+            ("ld %reg2 %reg5", 0x42869),
+            ("hlt", 0x0007f),
+        ];
+
+        for (text, word) in cases {
+            assert_eq!(parse_word(text).map(|iw| iw.buffer), Ok(word), "{}", text);
+        }
+
+        assert_eq!(
+            parse_instruction("ldc %reg0 -1"),
+            Ok(Operation::Load {
+                target_register: 0,
+                source: LoadSource::Constant(0xffff)
+            })
+        );
+        assert_eq!(parse_word("800a1").map(|iw| iw.buffer), Ok(0x800a1));
+
+        // Disassembly can be assembled again
+        for word in [
+            0x42104,
+            0xfff5b,
+            0x0045b,
+            0x01968,
+            0x42869,
+            0x00008 | 0x3 << 8 | 0x4 << 11,
+        ] {
+            let text = Operation::from(word).get_assembly_string();
+            assert_eq!(parse_word(&text).map(|iw| iw.buffer), Ok(word), "{}", text);
+        }
+
+        // Relative jumps used to be disassembled with the raw offset, which is one
+        // less than the distance written in sources, and tst with an unused target
+        assert_eq!(Operation::from(0xfff5b).get_assembly_string(), "jcr 0");
+        assert_ne!(parse_word("jcr -1").map(|iw| iw.buffer), Ok(0xfff5b));
+        assert!(parse_word("tst %reg0 %reg3 %reg4").is_err());
+
+        let mut symbols = SymbolTable::default();
+        symbols.insert("loop", 0x3).unwrap();
+        assert_eq!(
+            parse_word_at("jnzr loop", 0xa, &symbols).map(|iw| iw.buffer),
+            Ok(0xff85a)
+        );
+        assert_eq!(
+            parse_word_at("ldc %reg1 loop", 0x0, &symbols).map(|iw| iw.buffer),
+            Ok(0x00093)
+        );
+        assert!(parse_word_at("jnzr nowhere", 0xa, &symbols).is_err());
+
+        assert!(parse_instruction("").is_err());
+        assert!(parse_instruction("add %reg0 %reg1").is_err());
+        assert!(parse_instruction("mov %reg8 %reg0").is_err());
+        assert!(parse_instruction("ldc %reg0 0x10000").is_err());
+        assert!(parse_instruction("jzr 2049").is_err());
+        assert!(parse_instruction("hlt %reg0").is_err());
+        assert!(parse_instruction("frob").is_err());
+    }
+
+    fn words(program: &Program) -> Vec<u32> {
+        program
+            .instruction_words
+            .iter()
+            .map(|iw| iw.buffer)
+            .collect()
+    }
+
+    #[test]
+    fn assemble_source_test() {
+        // The example programs must assemble to the same words as with masm
+        let sources = [
+            (
+                include_str!("../../helpers/assembly/fib.s"),
+                include_str!("../../helpers/hex/fib.hex"),
+            ),
+            (
+                include_str!("../../helpers/assembly/tty_test_abc_endless.s"),
+                include_str!("../../helpers/hex/tty_test_abc_endless.hex"),
+            ),
+        ];
+        for (source, hex) in sources {
+            let expected: Vec<u32> = hex
+                .split_whitespace()
+                .filter_map(|word| u32::from_str_radix(word, 16).ok())
+                .collect();
+            assert_eq!(words(&assemble_source(source).unwrap()), expected);
+        }
+
+        let program = assemble_source(include_str!("../../helpers/assembly/fib.s")).unwrap();
+        assert_eq!(program.symbols.address_of("loop"), Some(0x3));
+        assert_eq!(program.symbols.address_of("end"), Some(0xb));
+        let source = program.source.as_ref().unwrap();
+        assert_eq!(
+            source.lines()[source.line_of(0x3).unwrap()].trim(),
+            "add %reg0 %reg0 %reg1"
+        );
+
+        let program = assemble_source("a: b: nop # Comment\n  jr a\nc:\n").unwrap();
+        assert_eq!(program.symbols.labels_at(0x0), ["a", "b"]);
+        assert_eq!(program.symbols.address_of("c"), Some(0x2));
+        assert_eq!(words(&program), [0x0006c, 0xffe58]);
+
+        assert_eq!(
+            assemble_source("nop\nfoo %reg1").err(),
+            Some(String::from("line 2: Unknown instruction 'foo'"))
+        );
+        assert!(assemble_source("a:\na:").is_err());
+    }
+}
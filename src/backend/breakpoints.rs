@@ -0,0 +1,491 @@
+use std::collections::btree_map::Entry;
+use std::collections::BTreeMap;
+
+use super::expression::{format_value, Expr};
+use super::runtime::CpuState;
+use super::symbols::SymbolTable;
+
+#[derive(Clone)]
+pub struct Condition {
+    pub source: String, // Keep the original text around for display purposes
+    pub expr: Expr,
+}
+
+impl Condition {
+    pub fn parse(source: &str) -> Result<Condition, String> {
+        Condition::parse_with(source, &SymbolTable::default())
+    }
+
+    // Labels are resolved when the condition is set, the source keeps their names
+    pub fn parse_with(source: &str, symbols: &SymbolTable) -> Result<Condition, String> {
+        let source = source.trim();
+        Ok(Condition {
+            source: String::from(source),
+            expr: Expr::parse_with(source, symbols)?,
+        })
+    }
+}
+
+// A log message with embedded expressions, e.g. `loop i={reg2:x} sum={reg5}`.
+// Supported formats are x (hex), b (binary), c (character) and d (decimal, default).
+// Literal braces are written as `{{` and `}}`.
+#[derive(Clone)]
+pub struct MessageTemplate {
+    pub source: String,
+    parts: Vec<TemplatePart>,
+}
+
+#[derive(Clone)]
+enum TemplatePart {
+    Text(String),
+    Value(Expr, char),
+}
+
+impl MessageTemplate {
+    pub fn parse(source: &str) -> Result<MessageTemplate, String> {
+        MessageTemplate::parse_with(source, &SymbolTable::default())
+    }
+
+    // Like conditions, labels are resolved when the template is parsed
+    pub fn parse_with(source: &str, symbols: &SymbolTable) -> Result<MessageTemplate, String> {
+        let mut parts = Vec::new();
+        let mut text = String::new();
+        let mut chars = source.chars().peekable();
+
+        while let Some(ch) = chars.next() {
+            match ch {
+                '{' if chars.peek() == Some(&'{') => {
+                    chars.next();
+                    text.push('{');
+                }
+                '}' if chars.peek() == Some(&'}') => {
+                    chars.next();
+                    text.push('}');
+                }
+                '{' => {
+                    let mut placeholder = String::new();
+                    loop {
+                        match chars.next() {
+                            Some('}') => break,
+                            Some(c) => placeholder.push(c),
+                            None => return Err(String::from("Unclosed '{'")),
+                        }
+                    }
+
+                    let (expr_text, format) = match placeholder.rsplit_once(':') {
+                        Some((expr_text, format)) => (expr_text, format.trim()),
+                        None => (placeholder.as_str(), "d"),
+                    };
+                    let format = match format {
+                        "x" | "b" | "c" | "d" => format.chars().next().unwrap(),
+                        _ => return Err(format!("Unknown format '{}'", format)),
+                    };
+
+                    if !text.is_empty() {
+                        parts.push(TemplatePart::Text(std::mem::take(&mut text)));
+                    }
+                    parts.push(TemplatePart::Value(
+                        Expr::parse_with(expr_text, symbols)?,
+                        format,
+                    ));
+                }
+                '}' => return Err(String::from("Unmatched '}'")),
+                _ => text.push(ch),
+            }
+        }
+
+        if !text.is_empty() {
+            parts.push(TemplatePart::Text(text));
+        }
+
+        Ok(MessageTemplate {
+            source: String::from(source),
+            parts,
+        })
+    }
+
+    pub fn format(&self, cpu: &CpuState) -> String {
+        self.parts
+            .iter()
+            .map(|part| match part {
+                TemplatePart::Text(text) => text.clone(),
+                TemplatePart::Value(expr, format) => format_value(expr.evaluate(cpu), *format),
+            })
+            .collect()
+    }
+}
+
+pub enum BreakpointKind {
+    Stop,
+    Log(MessageTemplate), // Tracepoint, logs a message instead of stopping
+}
+
+// What to do after the CPU arrived at an instruction
+#[derive(Debug, PartialEq, Eq)]
+pub enum BreakpointAction {
+    Continue,
+    Stop,
+    Log(String),
+}
+
+pub struct Breakpoint {
+    pub address: u16,
+    pub kind: BreakpointKind,
+    pub condition: Option<Condition>,
+    pub hit_count: u32, // Number of times the breakpoint was reached with its condition met
+    pub ignore_count: u32, // Number of hits to pass through before actually stopping
+    pub enabled: bool,
+}
+
+impl Breakpoint {
+    pub fn new(address: u16) -> Self {
+        Breakpoint {
+            address,
+            kind: BreakpointKind::Stop,
+            condition: None,
+            hit_count: 0,
+            ignore_count: 0,
+            enabled: true,
+        }
+    }
+
+    // A breakpoint that does not simply stop every time it is reached
+    pub fn is_conditional(&self) -> bool {
+        self.condition.is_some() || self.ignore_count > 0
+    }
+
+    pub fn is_tracepoint(&self) -> bool {
+        matches!(self.kind, BreakpointKind::Log(_))
+    }
+
+    pub fn describe(&self) -> String {
+        let mut description = format!("{:#06x}", self.address);
+
+        if let BreakpointKind::Log(template) = &self.kind {
+            description += &format!(" log \"{}\"", template.source);
+        }
+        if let Some(condition) = &self.condition {
+            description += &format!(" if {}", condition.source);
+        }
+        if self.ignore_count > 0 {
+            description += &format!(", ignore {}", self.ignore_count);
+        }
+        description += &format!(", hits {}", self.hit_count);
+        if !self.enabled {
+            description += " (disabled)";
+        }
+
+        description
+    }
+}
+
+#[derive(Default)]
+pub struct BreakpointManager {
+    breakpoints: BTreeMap<u16, Breakpoint>,
+}
+
+impl BreakpointManager {
+    pub fn get(&self, address: u16) -> Option<&Breakpoint> {
+        self.breakpoints.get(&address)
+    }
+
+    pub fn get_mut(&mut self, address: u16) -> Option<&mut Breakpoint> {
+        self.breakpoints.get_mut(&address)
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = &Breakpoint> {
+        self.breakpoints.values()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.breakpoints.is_empty()
+    }
+
+    // Returns the breakpoint at the given address, creating it if necessary
+    pub fn insert(&mut self, address: u16) -> &mut Breakpoint {
+        self.breakpoints
+            .entry(address)
+            .or_insert_with(|| Breakpoint::new(address))
+    }
+
+    pub fn remove(&mut self, address: u16) -> bool {
+        self.breakpoints.remove(&address).is_some()
+    }
+
+    pub fn clear(&mut self) {
+        self.breakpoints.clear();
+    }
+
+    // Returns true if a breakpoint is set at the address afterwards
+    pub fn toggle(&mut self, address: u16) -> bool {
+        if self.remove(address) {
+            false
+        } else {
+            self.insert(address);
+            true
+        }
+    }
+
+    // Set or clear (empty string) the condition of the breakpoint at the
+    // given address. The breakpoint is created if it does not exist yet.
+    pub fn set_condition(&mut self, address: u16, source: &str) -> Result<(), String> {
+        let condition = match source.trim() {
+            "" => None,
+            text => Some(Condition::parse(text)?),
+        };
+
+        self.insert(address).condition = condition;
+        Ok(())
+    }
+
+    // Turn the breakpoint into a tracepoint logging the given message, or back
+    // into a regular breakpoint if the message is empty
+    pub fn set_log_message(
+        &mut self,
+        address: u16,
+        source: &str,
+        symbols: &SymbolTable,
+    ) -> Result<(), String> {
+        let kind = match source {
+            "" => BreakpointKind::Stop,
+            text => BreakpointKind::Log(MessageTemplate::parse_with(text, symbols)?),
+        };
+
+        self.insert(address).kind = kind;
+        Ok(())
+    }
+
+    pub fn set_ignore_count(&mut self, address: u16, ignore_count: u32) {
+        self.insert(address).ignore_count = ignore_count;
+    }
+
+    // Move every breakpoint to a new address, e.g. after the program was reloaded.
+    // If several end up at the same address, the first one is kept and the others
+    // are returned with their old address, so the caller can report them.
+    pub fn relocate(&mut self, new_address: impl Fn(u16) -> u16) -> Vec<Breakpoint> {
+        let mut dropped = Vec::new();
+
+        for (address, mut breakpoint) in std::mem::take(&mut self.breakpoints) {
+            match self.breakpoints.entry(new_address(address)) {
+                Entry::Vacant(entry) => {
+                    breakpoint.address = *entry.key();
+                    entry.insert(breakpoint);
+                }
+                Entry::Occupied(_) => dropped.push(breakpoint),
+            }
+        }
+
+        dropped
+    }
+
+    // Parse conditions and log messages again, e.g. after the program was reloaded
+    // and its labels moved. The ones that no longer parse are removed, with a
+    // warning for each of them.
+    pub fn reparse(&mut self, symbols: &SymbolTable) -> Vec<String> {
+        let mut warnings = Vec::new();
+
+        for breakpoint in self.breakpoints.values_mut() {
+            if let Some(condition) = breakpoint.condition.take() {
+                match Condition::parse_with(&condition.source, symbols) {
+                    Ok(condition) => breakpoint.condition = Some(condition),
+                    Err(msg) => warnings.push(format!(
+                        "Removed condition '{}' of breakpoint {:#06x}: {}",
+                        condition.source, breakpoint.address, msg
+                    )),
+                }
+            }
+
+            if let BreakpointKind::Log(template) = &breakpoint.kind {
+                match MessageTemplate::parse_with(&template.source, symbols) {
+                    Ok(template) => breakpoint.kind = BreakpointKind::Log(template),
+                    Err(msg) => {
+                        warnings.push(format!(
+                            "Removed message '{}' of tracepoint {:#06x}, it stops instead: {}",
+                            template.source, breakpoint.address, msg
+                        ));
+                        breakpoint.kind = BreakpointKind::Stop;
+                    }
+                }
+            }
+        }
+
+        warnings
+    }
+
+    pub fn reset_hit_counts(&mut self) {
+        for breakpoint in self.breakpoints.values_mut() {
+            breakpoint.hit_count = 0;
+        }
+    }
+
+    // Called whenever the CPU arrives at a new instruction. Evaluates the condition
+    // of the breakpoint at the program counter (if any), updates its hit count and
+    // returns whether execution should stop or a message should be logged.
+    pub fn check(&mut self, cpu: &CpuState) -> BreakpointAction {
+        let breakpoint = match self.breakpoints.get_mut(&cpu.pcounter) {
+            Some(bp) if bp.enabled => bp,
+            _ => return BreakpointAction::Continue,
+        };
+
+        let condition_met = match &breakpoint.condition {
+            Some(condition) => condition.expr.is_true(cpu),
+            None => true,
+        };
+        if !condition_met {
+            return BreakpointAction::Continue;
+        }
+
+        breakpoint.hit_count += 1;
+        if breakpoint.hit_count <= breakpoint.ignore_count {
+            return BreakpointAction::Continue;
+        }
+
+        match &breakpoint.kind {
+            BreakpointKind::Stop => BreakpointAction::Stop,
+            BreakpointKind::Log(template) => BreakpointAction::Log(template.format(cpu)),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn toggle_test() {
+        let mut manager = BreakpointManager::default();
+
+        assert!(manager.toggle(0x5));
+        assert!(manager.get(0x5).is_some());
+        assert!(!manager.toggle(0x5));
+        assert!(manager.get(0x5).is_none());
+    }
+
+    #[test]
+    fn relocate_test() {
+        let mut manager = BreakpointManager::default();
+        manager.set_condition(0x5, "reg0 == 1").unwrap();
+        manager.insert(0x8);
+
+        assert!(manager.relocate(|address| address + 2).is_empty());
+        assert!(manager.get(0x5).is_none());
+        assert!(manager.get(0x7).unwrap().is_conditional());
+        assert_eq!(manager.get(0xa).map(|bp| bp.address), Some(0xa));
+
+        // Breakpoints moving onto the same address are not silently merged
+        let dropped = manager.relocate(|_| 0x3);
+        assert_eq!(dropped.len(), 1);
+        assert_eq!(dropped[0].address, 0xa);
+        assert!(manager.get(0x3).unwrap().is_conditional());
+    }
+
+    #[test]
+    fn reparse_test() {
+        let mut manager = BreakpointManager::default();
+        let mut cpu = CpuState::default();
+        let symbols = SymbolTable::parse("loop 0x3").unwrap();
+        manager.insert(0x3).condition =
+            Some(Condition::parse_with("pc == loop", &symbols).unwrap());
+        manager.set_log_message(0x4, "at {loop}", &symbols).unwrap();
+
+        // The condition follows the label to its new address
+        let moved = SymbolTable::parse("loop 0x5").unwrap();
+        assert!(manager.reparse(&moved).is_empty());
+        cpu.pcounter = 0x3;
+        assert_eq!(manager.check(&cpu), BreakpointAction::Continue);
+        cpu.pcounter = 0x4;
+        assert_eq!(
+            manager.check(&cpu),
+            BreakpointAction::Log(String::from("at 5"))
+        );
+        assert_eq!(
+            manager.get(0x3).unwrap().condition.as_ref().unwrap().source,
+            "pc == loop"
+        );
+
+        // Without the label, both are removed
+        assert_eq!(manager.reparse(&SymbolTable::default()).len(), 2);
+        assert!(manager.get(0x3).unwrap().condition.is_none());
+        assert!(!manager.get(0x4).unwrap().is_tracepoint());
+    }
+
+    #[test]
+    fn check_test() {
+        let mut manager = BreakpointManager::default();
+        let mut cpu = CpuState::default();
+
+        manager.insert(0x3);
+        manager.set_condition(0x4, "reg3 == 0x10 && carry").unwrap();
+        manager.set_ignore_count(0x5, 2);
+        assert!(manager.set_condition(0x6, "reg3 ==").is_err());
+
+        // Unconditional
+        cpu.pcounter = 0x3;
+        assert_eq!(manager.check(&cpu), BreakpointAction::Stop);
+        assert_eq!(manager.get(0x3).unwrap().hit_count, 1);
+
+        // No breakpoint
+        cpu.pcounter = 0x2;
+        assert_eq!(manager.check(&cpu), BreakpointAction::Continue);
+
+        // Conditional
+        cpu.pcounter = 0x4;
+        assert_eq!(manager.check(&cpu), BreakpointAction::Continue);
+        cpu.registers[3] = 0x10;
+        cpu.flags.carry = true;
+        assert_eq!(manager.check(&cpu), BreakpointAction::Stop);
+        assert_eq!(manager.get(0x4).unwrap().hit_count, 1);
+
+        // Ignore count
+        cpu.pcounter = 0x5;
+        assert_eq!(manager.check(&cpu), BreakpointAction::Continue);
+        assert_eq!(manager.check(&cpu), BreakpointAction::Continue);
+        assert_eq!(manager.check(&cpu), BreakpointAction::Stop);
+        assert_eq!(manager.get(0x5).unwrap().hit_count, 3);
+
+        // Disabled
+        manager.get_mut(0x3).unwrap().enabled = false;
+        cpu.pcounter = 0x3;
+        assert_eq!(manager.check(&cpu), BreakpointAction::Continue);
+
+        manager.reset_hit_counts();
+        assert!(manager.iter().all(|bp| bp.hit_count == 0));
+    }
+
+    #[test]
+    fn tracepoint_test() {
+        let mut manager = BreakpointManager::default();
+        let mut cpu = CpuState::default();
+        cpu.registers[2] = 0x1f;
+        cpu.registers[5] = 42;
+        cpu.pcounter = 0x7;
+        let symbols = SymbolTable::parse("letter 0x0").unwrap();
+
+        manager
+            .set_log_message(0x7, "loop i={reg2:x} sum={reg5} {{raw}}", &symbols)
+            .unwrap();
+        assert!(manager.get(0x7).unwrap().is_tracepoint());
+        assert_eq!(
+            manager.check(&cpu),
+            BreakpointAction::Log(String::from("loop i=0x1f sum=42 {raw}"))
+        );
+
+        manager
+            .set_log_message(0x7, "{reg5 == 42} {reg2:b} {[letter]:c}", &symbols)
+            .unwrap();
+        cpu.ram[0] = 'A' as u16;
+        assert_eq!(
+            manager.check(&cpu),
+            BreakpointAction::Log(String::from("1 0b11111 A"))
+        );
+
+        manager.set_log_message(0x7, "", &symbols).unwrap();
+        assert!(!manager.get(0x7).unwrap().is_tracepoint());
+
+        assert!(manager.set_log_message(0x8, "{reg2", &symbols).is_err());
+        assert!(manager.set_log_message(0x8, "{reg2:q}", &symbols).is_err());
+        assert!(manager.set_log_message(0x8, "reg2}", &symbols).is_err());
+        assert!(manager.set_log_message(0x8, "{reg9}", &symbols).is_err());
+        assert!(MessageTemplate::parse("{[letter]}").is_err());
+    }
+}
@@ -0,0 +1,122 @@
+// Remembers in which step registers, flags and RAM words were last changed by the
+// program, so the frontend can highlight recent changes. Steps are counted by
+// CpuState::executed, changes made by the user are not tracked.
+
+use std::collections::HashMap;
+
+use super::expression::Flag;
+use super::ir::Register;
+use super::runtime::{AccessKind, CpuState};
+
+// Register and flag values before an instruction, to compare against afterwards
+pub struct Snapshot {
+    registers: [u16; 8],
+    flags: [bool; 3],
+}
+
+fn flag_values(cpu: &CpuState) -> [bool; 3] {
+    [cpu.flags.zero, cpu.flags.carry, cpu.flags.overflow]
+}
+
+fn flag_index(flag: Flag) -> usize {
+    match flag {
+        Flag::Zero => 0,
+        Flag::Carry => 1,
+        Flag::Overflow => 2,
+    }
+}
+
+#[derive(Default)]
+pub struct ChangeTracker {
+    registers: [Option<u64>; 8],
+    flags: [Option<u64>; 3],
+    ram: HashMap<u16, u64>, // Written addresses, values are not compared
+    pub last_ram_write: Option<u16>,
+}
+
+impl ChangeTracker {
+    pub fn snapshot(cpu: &CpuState) -> Snapshot {
+        Snapshot {
+            registers: cpu.registers,
+            flags: flag_values(cpu),
+        }
+    }
+
+    // Record the changes of the instruction executed since the snapshot was taken
+    pub fn record(&mut self, before: &Snapshot, cpu: &CpuState) {
+        let step = cpu.executed;
+
+        for (reg, value) in cpu.registers.iter().enumerate() {
+            if before.registers[reg] != *value {
+                self.registers[reg] = Some(step);
+            }
+        }
+        for (index, value) in flag_values(cpu).iter().enumerate() {
+            if before.flags[index] != *value {
+                self.flags[index] = Some(step);
+            }
+        }
+
+        if let Some(access) = cpu.last_access.filter(|a| a.kind == AccessKind::Write) {
+            self.ram.insert(access.address, step);
+            self.last_ram_write = Some(access.address);
+        }
+    }
+
+    pub fn clear(&mut self) {
+        *self = ChangeTracker::default();
+    }
+
+    // Number of steps since the change, 0 if it was changed by the last step
+    fn age(step: Option<u64>, cpu: &CpuState) -> Option<u64> {
+        step.map(|step| cpu.executed.saturating_sub(step))
+    }
+
+    pub fn register_age(&self, reg: Register, cpu: &CpuState) -> Option<u64> {
+        Self::age(self.registers[reg], cpu)
+    }
+
+    pub fn flag_age(&self, flag: Flag, cpu: &CpuState) -> Option<u64> {
+        Self::age(self.flags[flag_index(flag)], cpu)
+    }
+
+    pub fn ram_age(&self, address: u16, cpu: &CpuState) -> Option<u64> {
+        Self::age(self.ram.get(&address).copied(), cpu)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::backend::program::Program;
+
+    /*
+    ldc %reg0 0x10
+    ldc %reg1 0x5
+    st %reg0 %reg1
+    */
+    const PMEM: [u32; 3] = [0x00180u32, 0x00095u32, 0x00168u32];
+
+    #[test]
+    fn record_test() {
+        let program = Program::from(PMEM.as_slice());
+        let mut cpu = CpuState::default();
+        let mut changes = ChangeTracker::default();
+
+        for _ in 0..3 {
+            let before = ChangeTracker::snapshot(&cpu);
+            cpu.execute_next_prog_op(&program);
+            changes.record(&before, &cpu);
+        }
+
+        assert_eq!(changes.register_age(0, &cpu), Some(2));
+        assert_eq!(changes.register_age(1, &cpu), Some(1));
+        assert_eq!(changes.register_age(2, &cpu), None);
+        assert_eq!(changes.ram_age(0x10, &cpu), Some(0));
+        assert_eq!(changes.ram_age(0x11, &cpu), None);
+        assert_eq!(changes.last_ram_write, Some(0x10));
+
+        changes.clear();
+        assert_eq!(changes.register_age(0, &cpu), None);
+    }
+}
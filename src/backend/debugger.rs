@@ -0,0 +1,348 @@
+use super::annotations::Annotations;
+use super::breakpoints::{BreakpointAction, BreakpointManager, Condition};
+use super::io_breaks::{describe_event, IoBreak};
+use super::ir::Operation;
+use super::program::Program;
+use super::runtime::{AccessKind, CpuState, IoEvent, MemoryAccess};
+use super::watchpoints::WatchpointManager;
+
+// Why the debugger wants execution to stop after an instruction
+pub enum StopReason {
+    Halt,
+    DebugInstruction {
+        pcounter: u16,
+    },
+    Breakpoint {
+        address: u16,
+        hit_count: u32,
+    },
+    Watchpoint {
+        description: String,
+        access: MemoryAccess,
+        pcounter: u16, // Address of the instruction performing the access
+    },
+    IoBreak {
+        io_break: IoBreak,
+        event: IoEvent,
+        pcounter: u16,
+    },
+}
+
+impl StopReason {
+    pub fn describe(&self) -> String {
+        match self {
+            StopReason::Halt => String::from("CPU received halt."),
+            StopReason::DebugInstruction { pcounter } => {
+                format!("Reached dbg instruction at {:#X}", pcounter)
+            }
+            StopReason::Breakpoint { address, hit_count } => {
+                format!("Reached breakpoint at {:#X} (hit {})", address, hit_count)
+            }
+            StopReason::Watchpoint {
+                description,
+                access,
+                pcounter,
+            } => {
+                let action = match access.kind {
+                    AccessKind::Read => "read",
+                    AccessKind::Write => "write",
+                };
+                format!(
+                    "Watchpoint {}: {} {:#06x} at {:#06x} by instruction {:#X}",
+                    description, action, access.value, access.address, pcounter
+                )
+            }
+            StopReason::IoBreak {
+                io_break,
+                event,
+                pcounter,
+            } => format!(
+                "IO break on {}: {} by instruction {:#X}",
+                io_break.describe(),
+                describe_event(event),
+                pcounter
+            ),
+        }
+    }
+}
+
+// Where to stop when running for a limited time instead of freely
+pub enum RunTarget {
+    Address(u16),
+    Steps(u32), // Remaining number of instructions
+    Condition(Condition),
+    LeaveRange { start: u16, end: u16 }, // Inclusive, e.g. the body of a loop
+}
+
+impl RunTarget {
+    // Called after every executed instruction
+    pub fn is_reached(&mut self, cpu: &CpuState) -> bool {
+        match self {
+            RunTarget::Address(address) => cpu.pcounter == *address,
+            RunTarget::Steps(remaining) => {
+                *remaining = remaining.saturating_sub(1);
+                *remaining == 0
+            }
+            RunTarget::Condition(condition) => condition.expr.is_true(cpu),
+            RunTarget::LeaveRange { start, end } => !(*start..=*end).contains(&cpu.pcounter),
+        }
+    }
+
+    pub fn describe(&self) -> String {
+        match self {
+            RunTarget::Address(address) => format!("address {:#X}", address),
+            RunTarget::Steps(remaining) => format!("{} more instruction(s)", remaining),
+            RunTarget::Condition(condition) => format!("condition {}", condition.source),
+            RunTarget::LeaveRange { start, end } => {
+                format!("leaving loop {:#X}..{:#X}", start, end)
+            }
+        }
+    }
+}
+
+// Bundles everything that can interrupt program execution
+#[derive(Default)]
+pub struct Debugger {
+    pub breakpoints: BreakpointManager,
+    pub watchpoints: WatchpointManager,
+    pub io_breaks: Vec<IoBreak>,
+    pub annotations: Annotations, // Bookmarks and comments, they never stop execution
+
+    // Messages produced by tracepoints, to be picked up by the frontend
+    trace_messages: Vec<String>,
+}
+
+impl Debugger {
+    // Execute the next instruction and check all stop conditions afterwards
+    pub fn step(&mut self, cpu: &mut CpuState, program: &Program) -> Option<StopReason> {
+        let pcounter = cpu.pcounter;
+        cpu.execute_next_prog_op(program);
+
+        if cpu.received_halt {
+            return Some(StopReason::Halt);
+        }
+
+        let debug_reason = match program.operations.get(pcounter as usize) {
+            Some(Operation::Debug) => Some(StopReason::DebugInstruction { pcounter }),
+            _ => None,
+        };
+
+        let watchpoint_reason = cpu.last_access.and_then(|access| {
+            let index = self.watchpoints.check(&access)?;
+            Some(StopReason::Watchpoint {
+                description: self.watchpoints.get(index)?.describe(),
+                access,
+                pcounter,
+            })
+        });
+
+        let io_reason = cpu.last_io_event.and_then(|event| {
+            let io_break = self.io_breaks.iter().find(|b| b.matches(&event))?;
+            Some(StopReason::IoBreak {
+                io_break: *io_break,
+                event,
+                pcounter,
+            })
+        });
+
+        // Always check the breakpoint, even if a watchpoint triggered, so hit counts stay correct
+        let breakpoint_reason = match self.breakpoints.check(cpu) {
+            BreakpointAction::Continue => None,
+            BreakpointAction::Stop => Some(StopReason::Breakpoint {
+                address: cpu.pcounter,
+                hit_count: self
+                    .breakpoints
+                    .get(cpu.pcounter)
+                    .map_or(0, |bp| bp.hit_count),
+            }),
+            BreakpointAction::Log(message) => {
+                self.trace_messages
+                    .push(format!("[{:#06x}] {}", cpu.pcounter, message));
+                None
+            }
+        };
+
+        debug_reason
+            .or(watchpoint_reason)
+            .or(io_reason)
+            .or(breakpoint_reason)
+    }
+
+    pub fn take_trace_messages(&mut self) -> Vec<String> {
+        std::mem::take(&mut self.trace_messages)
+    }
+
+    // Returns true if the IO break is set afterwards
+    pub fn toggle_io_break(&mut self, io_break: IoBreak) -> bool {
+        match self.io_breaks.iter().position(|b| *b == io_break) {
+            Some(index) => {
+                self.io_breaks.remove(index);
+                false
+            }
+            None => {
+                self.io_breaks.push(io_break);
+                true
+            }
+        }
+    }
+
+    pub fn reset_hit_counts(&mut self) {
+        self.breakpoints.reset_hit_counts();
+        self.watchpoints.reset_hit_counts();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::backend::watchpoints::Watchpoint;
+
+    /*
+    ldc %reg0 0x10
+    ldc %reg1 0x5
+    st %reg0 %reg1
+    ld %reg2 %reg0
+    hlt
+    */
+    const PMEM: [u32; 5] = [0x00180u32, 0x00095u32, 0x00168u32, 0x40069u32, 0x0007fu32];
+
+    #[test]
+    fn step_test() {
+        let program = Program::from(PMEM.as_slice());
+        let mut cpu = CpuState::default();
+        let mut debugger = Debugger::default();
+
+        debugger
+            .watchpoints
+            .add(Watchpoint::parse("0x10 w=5").unwrap());
+        debugger.breakpoints.insert(0x4);
+
+        assert!(debugger.step(&mut cpu, &program).is_none());
+        assert!(debugger.step(&mut cpu, &program).is_none());
+
+        match debugger.step(&mut cpu, &program) {
+            Some(StopReason::Watchpoint {
+                access, pcounter, ..
+            }) => {
+                assert_eq!(access.kind, AccessKind::Write);
+                assert_eq!(access.address, 0x10);
+                assert_eq!(access.value, 0x5);
+                assert_eq!(pcounter, 0x2);
+            }
+            _ => panic!("Expected watchpoint"),
+        }
+
+        match debugger.step(&mut cpu, &program) {
+            Some(StopReason::Breakpoint { address, hit_count }) => {
+                assert_eq!(address, 0x4);
+                assert_eq!(hit_count, 1);
+                assert_eq!(cpu.registers[2], 0x5);
+            }
+            _ => panic!("Expected breakpoint"),
+        }
+
+        assert!(matches!(
+            debugger.step(&mut cpu, &program),
+            Some(StopReason::Halt)
+        ));
+    }
+
+    /*
+    ldc %reg1 0x8002
+    ld %reg0 %reg1
+    ldc %reg1 0x8000
+    st %reg1 %reg0
+    hlt
+    */
+    const PMEM_IO: [u32; 5] = [0x80092u32, 0x00869u32, 0x80090u32, 0x00868u32, 0x0007fu32];
+
+    #[test]
+    fn io_break_test() {
+        let program = Program::from(PMEM_IO.as_slice());
+        let mut debugger = Debugger::default();
+
+        assert!(debugger.toggle_io_break(IoBreak::EmptyInputRead));
+        assert!(debugger.toggle_io_break(IoBreak::TerminalWrite(Some('x'))));
+
+        // Input available, so the read does not trigger but the write does
+        let mut cpu = CpuState::default();
+        cpu.istream.string = String::from("x");
+        assert!(debugger.step(&mut cpu, &program).is_none());
+        assert!(debugger.step(&mut cpu, &program).is_none());
+        assert!(debugger.step(&mut cpu, &program).is_none());
+        assert!(matches!(
+            debugger.step(&mut cpu, &program),
+            Some(StopReason::IoBreak {
+                event: IoEvent::TerminalWrite('x'),
+                ..
+            })
+        ));
+
+        // Empty input triggers on read
+        let mut cpu = CpuState::default();
+        assert!(debugger.step(&mut cpu, &program).is_none());
+        assert!(matches!(
+            debugger.step(&mut cpu, &program),
+            Some(StopReason::IoBreak {
+                io_break: IoBreak::EmptyInputRead,
+                pcounter: 0x1,
+                ..
+            })
+        ));
+
+        assert!(!debugger.toggle_io_break(IoBreak::EmptyInputRead));
+        assert_eq!(debugger.io_breaks.len(), 1);
+    }
+
+    /*
+    ldc %reg0 0x1
+    dbg
+    hlt
+    */
+    const PMEM_DBG: [u32; 3] = [0x00081u32, 0x0007eu32, 0x0007fu32];
+
+    #[test]
+    fn debug_instruction_test() {
+        let program = Program::from(PMEM_DBG.as_slice());
+        let mut cpu = CpuState::default();
+        let mut debugger = Debugger::default();
+
+        assert!(debugger.step(&mut cpu, &program).is_none());
+        assert!(matches!(
+            debugger.step(&mut cpu, &program),
+            Some(StopReason::DebugInstruction { pcounter: 0x1 })
+        ));
+        assert_eq!(cpu.pcounter, 0x2);
+        assert!(matches!(
+            debugger.step(&mut cpu, &program),
+            Some(StopReason::Halt)
+        ));
+    }
+
+    #[test]
+    fn run_target_test() {
+        let mut cpu = CpuState::default();
+
+        let mut steps = RunTarget::Steps(2);
+        assert!(!steps.is_reached(&cpu));
+        assert!(steps.is_reached(&cpu));
+
+        let mut address = RunTarget::Address(0x4);
+        assert!(!address.is_reached(&cpu));
+        cpu.pcounter = 0x4;
+        assert!(address.is_reached(&cpu));
+
+        let mut leave = RunTarget::LeaveRange {
+            start: 0x3,
+            end: 0x5,
+        };
+        assert!(!leave.is_reached(&cpu));
+        cpu.pcounter = 0x6;
+        assert!(leave.is_reached(&cpu));
+
+        let mut condition = RunTarget::Condition(Condition::parse("reg1 == 2").unwrap());
+        assert!(!condition.is_reached(&cpu));
+        cpu.registers[1] = 2;
+        assert!(condition.is_reached(&cpu));
+    }
+}
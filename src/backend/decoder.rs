@@ -272,8 +272,7 @@ impl From<InstructionWord> for ir::Operation {
             },
 
             Opcode::NOP => Operation::Noop,
-            // The DGB opcode really does not matter to our emu at the moment
-            Opcode::DBG => Operation::Noop,
+            Opcode::DBG => Operation::Debug,
             Opcode::HLT => Operation::Halt,
 
             Opcode::LDC => Operation::Load {
@@ -284,6 +283,107 @@ impl From<InstructionWord> for ir::Operation {
     }
 }
 
+// Encode an operation into an instruction word, the inverse of the above
+impl From<&Operation> for InstructionWord {
+    fn from(op: &Operation) -> Self {
+        let unary = |opcode: Opcode, op: &UnaryOp| {
+            opcode as u32 | (op.source_a as u32) << 8 | (op.target as u32) << 17
+        };
+        let binary = |opcode: Opcode, op: &BinaryOp| {
+            opcode as u32
+                | (op.source_a as u32) << 8
+                | (op.source_b as u32) << 11
+                | (op.target as u32) << 17
+        };
+        let relative_jump =
+            |opcode: Opcode, offset: i16| opcode as u32 | ((offset as u16 as u32) & 0xFFF) << 8;
+
+        let buffer = match op {
+            Operation::Add(o) => binary(Opcode::ADD, o),
+            Operation::Add3(o) => {
+                Opcode::ADD3 as u32
+                    | (o.source_a as u32) << 8
+                    | (o.source_b as u32) << 11
+                    | (o.source_c as u32) << 14
+                    | (o.target as u32) << 17
+            }
+            Operation::AddCarry(o) => binary(Opcode::ADC, o),
+            Operation::Sub(o) => binary(Opcode::SUB, o),
+            Operation::SubCarry(o) => binary(Opcode::SUBC, o),
+            Operation::Inc(o) => unary(Opcode::INC, o),
+            Operation::Dec(o) => unary(Opcode::DEC, o),
+            Operation::Multiply(o) => binary(Opcode::MUL, o),
+            Operation::Test(o) => binary(Opcode::TST, o),
+            Operation::And(o) => binary(Opcode::AND, o),
+            Operation::Or(o) => binary(Opcode::OR, o),
+            Operation::Not(o) => unary(Opcode::NOT, o),
+            Operation::Neg(o) => unary(Opcode::NEG, o),
+            Operation::Xor(o) => binary(Opcode::XOR, o),
+            Operation::Xnor(o) => binary(Opcode::XNOR, o),
+            Operation::ShiftLeft(o) => binary(Opcode::SHL, o),
+            Operation::ShiftRight(o) => binary(Opcode::SHR, o),
+            Operation::Move(o) => unary(Opcode::MOV, o),
+
+            Operation::Jump {
+                target: JumpTarget::AbsoluteAdressRegister(reg),
+                condition,
+            } => {
+                let opcode = match condition {
+                    JumpCondition::Always => Opcode::JMP,
+                    JumpCondition::Zero => Opcode::JZ,
+                    JumpCondition::NotZero => Opcode::JNZ,
+                    JumpCondition::Carry => Opcode::JC,
+                    JumpCondition::NotCarry => Opcode::JNC,
+                };
+                opcode as u32 | (*reg as u32) << 8
+            }
+            Operation::Jump {
+                target: JumpTarget::AddressOffsetConstant(offset),
+                condition,
+            } => {
+                let opcode = match condition {
+                    JumpCondition::Always => Opcode::JRCON,
+                    JumpCondition::Zero => Opcode::JZR,
+                    JumpCondition::NotZero => Opcode::JNZR,
+                    JumpCondition::Carry => Opcode::JCR,
+                    JumpCondition::NotCarry => Opcode::JNCR,
+                };
+                relative_jump(opcode, *offset)
+            }
+
+            Operation::Store {
+                address_register,
+                data_register,
+            } => {
+                Opcode::ST as u32 | (*data_register as u32) << 8 | (*address_register as u32) << 11
+            }
+            Operation::Load {
+                target_register,
+                source: LoadSource::RAM { address_register },
+            } => {
+                Opcode::LD as u32
+                    | (*address_register as u32) << 11
+                    | (*target_register as u32) << 17
+            }
+            Operation::Load {
+                target_register,
+                source: LoadSource::Constant(constant),
+            } => {
+                Opcode::LDC as u32
+                    | (*target_register as u32) << 4
+                    | (*constant as u32 & 0xF)
+                    | (*constant as u32 >> 4) << 8
+            }
+
+            Operation::Noop => Opcode::NOP as u32,
+            Operation::Debug => Opcode::DBG as u32,
+            Operation::Halt => Opcode::HLT as u32,
+        };
+
+        InstructionWord { buffer }
+    }
+}
+
 impl From<u32> for Operation {
     fn from(value: u32) -> Self {
         Operation::from(InstructionWord::from(value))
@@ -345,6 +445,18 @@ mod tests {
     const INSTR13: u32 = 0x01968; // st %reg3 %reg1
     const INSTR14: u32 = 0x42869; // ld %reg2 %reg5
     const INSTRH: u32 = 0x0007f; // halt
+    const INSTRD: u32 = 0x0007e; // dbg
+
+    #[test]
+    fn encode_test() {
+        for word in [
+            INSTR1, INSTR2, INSTR3, INSTR4, INSTR5, INSTR6, INSTR7, INSTR8, INSTR9, INSTR10,
+            INSTR11, INSTR12, INSTR13, INSTR14, INSTRH, INSTRD, 0xffd58,
+        ] {
+            let op = Operation::from(word);
+            assert_eq!(InstructionWord::from(&op).buffer, word, "{:?}", op);
+        }
+    }
 
     #[test]
     fn instructionword_getter_test() {
@@ -556,5 +668,12 @@ mod tests {
 
         let oph = Operation::from(wordh);
         assert_eq!(oph, Operation::Halt);
+
+        let wordd = InstructionWord::from(INSTRD);
+        assert_eq!(wordd.get_opcode(), Opcode::DBG);
+
+        let opd = Operation::from(wordd);
+        assert_eq!(opd, Operation::Debug);
+        assert_eq!(opd.get_assembly_string(), "dbg");
     }
 }
@@ -0,0 +1,555 @@
+// A small expression language over the CPU state, used for breakpoint conditions
+// and every numeric input of the debugger.
+//
+// Examples: `reg3 == 0x10 && carry`, `ram[0x20] != 0 || !zero`, `pc >= 0b1010`,
+// `[reg2 + 4] & 0xff`, `buffer + reg1 * 2` (with a label `buffer`)
+//
+// All values are u16 and arithmetic wraps around. Comparisons and logical
+// operators evaluate to 1 or 0, and any non-zero value counts as true. `[x]` is
+// short for `ram[x]`. Operator precedence follows Rust, so `reg1 & 4 == 4`
+// masks first. Division by zero gives 0, evaluation never fails.
+
+use num_traits::{Num, Unsigned};
+
+use super::ir::Register;
+use super::runtime::{CpuState, RAM_SIZE};
+use super::symbols::SymbolTable;
+
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+pub enum Flag {
+    Zero,
+    Carry,
+    Overflow,
+}
+
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+pub enum UnaryOperator {
+    Not,    // Logical, !
+    BitNot, // ~
+    Negate, // -
+}
+
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+pub enum BinaryOperator {
+    Equal,
+    NotEqual,
+    Less,
+    LessEqual,
+    Greater,
+    GreaterEqual,
+    And,
+    Or,
+    Add,
+    Subtract,
+    Multiply,
+    Divide,
+    Remainder,
+    BitAnd,
+    BitOr,
+    BitXor,
+    ShiftLeft,
+    ShiftRight,
+}
+
+impl BinaryOperator {
+    // Binding strength, higher binds tighter
+    fn precedence(&self) -> u8 {
+        match self {
+            BinaryOperator::Or => 1,
+            BinaryOperator::And => 2,
+            BinaryOperator::Equal | BinaryOperator::NotEqual => 3,
+            BinaryOperator::Less
+            | BinaryOperator::LessEqual
+            | BinaryOperator::Greater
+            | BinaryOperator::GreaterEqual => 4,
+            BinaryOperator::BitOr => 5,
+            BinaryOperator::BitXor => 6,
+            BinaryOperator::BitAnd => 7,
+            BinaryOperator::ShiftLeft | BinaryOperator::ShiftRight => 8,
+            BinaryOperator::Add | BinaryOperator::Subtract => 9,
+            BinaryOperator::Multiply | BinaryOperator::Divide | BinaryOperator::Remainder => 10,
+        }
+    }
+
+    fn apply(&self, lhs: u16, rhs: u16) -> u16 {
+        let res = match self {
+            BinaryOperator::Add => return lhs.wrapping_add(rhs),
+            BinaryOperator::Subtract => return lhs.wrapping_sub(rhs),
+            BinaryOperator::Multiply => return lhs.wrapping_mul(rhs),
+            BinaryOperator::Divide => return lhs.checked_div(rhs).unwrap_or(0),
+            BinaryOperator::Remainder => return lhs.checked_rem(rhs).unwrap_or(0),
+            BinaryOperator::BitAnd => return lhs & rhs,
+            BinaryOperator::BitOr => return lhs | rhs,
+            BinaryOperator::BitXor => return lhs ^ rhs,
+            BinaryOperator::ShiftLeft => return lhs.checked_shl(rhs as u32).unwrap_or(0),
+            BinaryOperator::ShiftRight => return lhs.checked_shr(rhs as u32).unwrap_or(0),
+            BinaryOperator::Equal => lhs == rhs,
+            BinaryOperator::NotEqual => lhs != rhs,
+            BinaryOperator::Less => lhs < rhs,
+            BinaryOperator::LessEqual => lhs <= rhs,
+            BinaryOperator::Greater => lhs > rhs,
+            BinaryOperator::GreaterEqual => lhs >= rhs,
+            BinaryOperator::And => lhs != 0 && rhs != 0,
+            BinaryOperator::Or => lhs != 0 || rhs != 0,
+        };
+        res as u16
+    }
+}
+
+impl UnaryOperator {
+    fn apply(&self, operand: u16) -> u16 {
+        match self {
+            UnaryOperator::Not => (operand == 0) as u16,
+            UnaryOperator::BitNot => !operand,
+            UnaryOperator::Negate => operand.wrapping_neg(),
+        }
+    }
+}
+
+#[derive(Debug, PartialEq, Eq, Clone)]
+pub enum Expr {
+    Constant(u16),
+    Register(Register),
+    ProgramCounter,
+    Flag(Flag),
+    Ram(Box<Expr>),
+    Unary(UnaryOperator, Box<Expr>),
+    Binary(BinaryOperator, Box<Expr>, Box<Expr>),
+}
+
+impl Expr {
+    pub fn parse(text: &str) -> Result<Expr, String> {
+        Expr::parse_with(text, &SymbolTable::default())
+    }
+
+    // Labels are replaced by their address while parsing
+    pub fn parse_with(text: &str, symbols: &SymbolTable) -> Result<Expr, String> {
+        let tokens = tokenize(text)?;
+        if tokens.is_empty() {
+            return Err(String::from("Empty expression"));
+        }
+
+        let mut parser = Parser {
+            tokens,
+            pos: 0,
+            symbols,
+        };
+        let expr = parser.parse_binary(0)?;
+
+        match parser.peek() {
+            None => Ok(expr),
+            Some(token) => Err(format!("Unexpected '{}'", token)),
+        }
+    }
+
+    pub fn evaluate(&self, cpu: &CpuState) -> u16 {
+        match self {
+            Expr::Constant(value) => *value,
+            Expr::Register(reg) => cpu.registers[*reg],
+            Expr::ProgramCounter => cpu.pcounter,
+            Expr::Flag(flag) => match flag {
+                Flag::Zero => cpu.flags.zero as u16,
+                Flag::Carry => cpu.flags.carry as u16,
+                Flag::Overflow => cpu.flags.overflow as u16,
+            },
+            Expr::Ram(address) => {
+                // Reading the memory mapped IO region would have side effects,
+                // so anything outside of RAM simply reads as 0
+                let address = address.evaluate(cpu) as usize;
+                match address < RAM_SIZE {
+                    true => cpu.ram[address],
+                    false => 0,
+                }
+            }
+            Expr::Unary(op, operand) => op.apply(operand.evaluate(cpu)),
+            Expr::Binary(op, lhs, rhs) => op.apply(lhs.evaluate(cpu), rhs.evaluate(cpu)),
+        }
+    }
+
+    // The value of an expression not depending on the CPU state
+    pub fn constant_value(&self) -> Option<u16> {
+        match self {
+            Expr::Constant(value) => Some(*value),
+            Expr::Register(_) | Expr::ProgramCounter | Expr::Flag(_) | Expr::Ram(_) => None,
+            Expr::Unary(op, operand) => Some(op.apply(operand.constant_value()?)),
+            Expr::Binary(op, lhs, rhs) => {
+                Some(op.apply(lhs.constant_value()?, rhs.constant_value()?))
+            }
+        }
+    }
+
+    pub fn is_true(&self, cpu: &CpuState) -> bool {
+        self.evaluate(cpu) != 0
+    }
+}
+
+#[derive(Debug, PartialEq, Eq, Clone)]
+enum Token {
+    Number(u16),
+    Identifier(String),
+    Operator(&'static str),
+    LeftParen,
+    RightParen,
+    LeftBracket,
+    RightBracket,
+}
+
+impl std::fmt::Display for Token {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Token::Number(n) => write!(f, "{}", n),
+            Token::Identifier(ident) => write!(f, "{}", ident),
+            Token::Operator(op) => write!(f, "{}", op),
+            Token::LeftParen => write!(f, "("),
+            Token::RightParen => write!(f, ")"),
+            Token::LeftBracket => write!(f, "["),
+            Token::RightBracket => write!(f, "]"),
+        }
+    }
+}
+
+// Longer operators first, so that e.g. "<=" is not lexed as "<" followed by "="
+const OPERATORS: [&str; 20] = [
+    "==", "!=", "<=", ">=", "&&", "||", "<<", ">>", "<", ">", "!", "~", "+", "-", "*", "/", "%",
+    "&", "|", "^",
+];
+
+// Parse an unsigned number literal in hex (0x), binary (0b) or decimal notation.
+// Values are u16 in expressions, other callers may ask for wider types.
+pub fn parse_number<T: Num + Unsigned>(text: &str) -> Result<T, String> {
+    let lowercase = text.to_lowercase();
+    let res = if let Some(digits) = lowercase.strip_prefix("0x") {
+        T::from_str_radix(digits, 16)
+    } else if let Some(digits) = lowercase.strip_prefix("0b") {
+        T::from_str_radix(digits, 2)
+    } else {
+        T::from_str_radix(&lowercase, 10)
+    };
+
+    res.map_err(|_| format!("Invalid number '{}'", text))
+}
+
+// Format a value for display: x (hex), b (binary), c (character) or d (decimal)
+pub fn format_value(value: u16, format: char) -> String {
+    match format {
+        'x' => format!("{:#x}", value),
+        'b' => format!("{:#b}", value),
+        'c' => char::from_u32(value as u32).unwrap_or('?').to_string(),
+        _ => format!("{}", value),
+    }
+}
+
+// Evaluate an expression against the current CPU state, e.g. user input
+pub fn evaluate(text: &str, cpu: &CpuState, symbols: &SymbolTable) -> Result<u16, String> {
+    Ok(Expr::parse_with(text, symbols)?.evaluate(cpu))
+}
+
+// Evaluate an expression which may only use numbers, labels and operators,
+// for values that are fixed when they are entered like breakpoint addresses
+pub fn evaluate_constant(text: &str, symbols: &SymbolTable) -> Result<u16, String> {
+    Expr::parse_with(text, symbols)?
+        .constant_value()
+        .ok_or_else(|| {
+            format!(
+                "'{}' must not depend on registers, flags or RAM",
+                text.trim()
+            )
+        })
+}
+
+// Parse an inclusive address range of the form `<start>..<end>`, or a single address
+pub fn parse_range(text: &str) -> Result<(u16, u16), String> {
+    parse_range_with(text, &SymbolTable::default())
+}
+
+// Like parse_range, but both ends may be constant expressions using labels
+pub fn parse_range_with(text: &str, symbols: &SymbolTable) -> Result<(u16, u16), String> {
+    let (start, end) = match text.split_once("..") {
+        Some((start, end)) => (
+            evaluate_constant(start, symbols)?,
+            evaluate_constant(end, symbols)?,
+        ),
+        None => {
+            let address = evaluate_constant(text, symbols)?;
+            (address, address)
+        }
+    };
+
+    match start <= end {
+        true => Ok((start, end)),
+        false => Err(format!("Empty address range '{}'", text)),
+    }
+}
+
+fn tokenize(text: &str) -> Result<Vec<Token>, String> {
+    let chars: Vec<char> = text.chars().collect();
+    let mut tokens = Vec::new();
+    let mut i = 0;
+
+    while i < chars.len() {
+        let ch = chars[i];
+
+        if ch.is_whitespace() {
+            i += 1;
+            continue;
+        }
+
+        // Numbers and identifiers are both runs of alphanumeric characters,
+        // labels may also contain dots
+        let is_word_char = |ch: char| ch.is_ascii_alphanumeric() || ch == '_' || ch == '.';
+        if is_word_char(ch) && !chars[i..].starts_with(&['.', '.']) {
+            let start = i;
+            while i < chars.len() && is_word_char(chars[i]) {
+                i += 1;
+            }
+            let word: String = chars[start..i].iter().collect();
+
+            if ch.is_ascii_digit() {
+                tokens.push(Token::Number(parse_number(&word)?));
+            } else {
+                tokens.push(Token::Identifier(word));
+            }
+            continue;
+        }
+
+        let single = match ch {
+            '(' => Some(Token::LeftParen),
+            ')' => Some(Token::RightParen),
+            '[' => Some(Token::LeftBracket),
+            ']' => Some(Token::RightBracket),
+            _ => None,
+        };
+        if let Some(token) = single {
+            tokens.push(token);
+            i += 1;
+            continue;
+        }
+
+        let rest: String = chars[i..].iter().collect();
+        match OPERATORS.iter().find(|op| rest.starts_with(*op)) {
+            Some(op) => {
+                tokens.push(Token::Operator(op));
+                i += op.len();
+            }
+            None => return Err(format!("Unexpected character '{}'", ch)),
+        }
+    }
+
+    Ok(tokens)
+}
+
+struct Parser<'a> {
+    tokens: Vec<Token>,
+    pos: usize,
+    symbols: &'a SymbolTable,
+}
+
+impl<'a> Parser<'a> {
+    fn peek(&self) -> Option<&Token> {
+        self.tokens.get(self.pos)
+    }
+
+    fn next(&mut self) -> Option<Token> {
+        let token = self.tokens.get(self.pos).cloned();
+        self.pos += 1;
+        token
+    }
+
+    fn expect(&mut self, expected: Token) -> Result<(), String> {
+        match self.next() {
+            Some(token) if token == expected => Ok(()),
+            Some(token) => Err(format!("Expected '{}', found '{}'", expected, token)),
+            None => Err(format!("Expected '{}'", expected)),
+        }
+    }
+
+    fn peek_binary_operator(&self) -> Option<BinaryOperator> {
+        match self.peek() {
+            Some(Token::Operator(op)) => match *op {
+                "==" => Some(BinaryOperator::Equal),
+                "!=" => Some(BinaryOperator::NotEqual),
+                "<" => Some(BinaryOperator::Less),
+                "<=" => Some(BinaryOperator::LessEqual),
+                ">" => Some(BinaryOperator::Greater),
+                ">=" => Some(BinaryOperator::GreaterEqual),
+                "&&" => Some(BinaryOperator::And),
+                "||" => Some(BinaryOperator::Or),
+                "+" => Some(BinaryOperator::Add),
+                "-" => Some(BinaryOperator::Subtract),
+                "*" => Some(BinaryOperator::Multiply),
+                "/" => Some(BinaryOperator::Divide),
+                "%" => Some(BinaryOperator::Remainder),
+                "&" => Some(BinaryOperator::BitAnd),
+                "|" => Some(BinaryOperator::BitOr),
+                "^" => Some(BinaryOperator::BitXor),
+                "<<" => Some(BinaryOperator::ShiftLeft),
+                ">>" => Some(BinaryOperator::ShiftRight),
+                _ => None,
+            },
+            _ => None,
+        }
+    }
+
+    // Precedence climbing, all binary operators are left associative
+    fn parse_binary(&mut self, min_precedence: u8) -> Result<Expr, String> {
+        let mut lhs = self.parse_unary()?;
+
+        while let Some(op) = self.peek_binary_operator() {
+            if op.precedence() < min_precedence {
+                break;
+            }
+            self.pos += 1;
+
+            let rhs = self.parse_binary(op.precedence() + 1)?;
+            lhs = Expr::Binary(op, Box::new(lhs), Box::new(rhs));
+        }
+
+        Ok(lhs)
+    }
+
+    fn parse_unary(&mut self) -> Result<Expr, String> {
+        let op = match self.peek() {
+            Some(Token::Operator("!")) => Some(UnaryOperator::Not),
+            Some(Token::Operator("~")) => Some(UnaryOperator::BitNot),
+            Some(Token::Operator("-")) => Some(UnaryOperator::Negate),
+            _ => None,
+        };
+        if let Some(op) = op {
+            self.pos += 1;
+            let operand = self.parse_unary()?;
+            return Ok(Expr::Unary(op, Box::new(operand)));
+        }
+
+        self.parse_primary()
+    }
+
+    fn parse_primary(&mut self) -> Result<Expr, String> {
+        match self.next() {
+            Some(Token::Number(n)) => Ok(Expr::Constant(n)),
+            Some(Token::LeftParen) => {
+                let expr = self.parse_binary(0)?;
+                self.expect(Token::RightParen)?;
+                Ok(expr)
+            }
+            Some(Token::LeftBracket) => {
+                let address = self.parse_binary(0)?;
+                self.expect(Token::RightBracket)?;
+                Ok(Expr::Ram(Box::new(address)))
+            }
+            Some(Token::Identifier(ident)) => self.parse_identifier(&ident),
+            Some(token) => Err(format!("Unexpected '{}'", token)),
+            None => Err(String::from("Unexpected end of expression")),
+        }
+    }
+
+    fn parse_identifier(&mut self, ident: &str) -> Result<Expr, String> {
+        match ident.to_lowercase().as_str() {
+            "pc" => Ok(Expr::ProgramCounter),
+            "zero" => Ok(Expr::Flag(Flag::Zero)),
+            "carry" => Ok(Expr::Flag(Flag::Carry)),
+            "overflow" => Ok(Expr::Flag(Flag::Overflow)),
+            "ram" => {
+                self.expect(Token::LeftBracket)?;
+                let address = self.parse_binary(0)?;
+                self.expect(Token::RightBracket)?;
+                Ok(Expr::Ram(Box::new(address)))
+            }
+            name => match name.strip_prefix("reg").map(str::parse::<usize>) {
+                Some(Ok(reg)) if reg < 8 => Ok(Expr::Register(reg)),
+                _ => match self.symbols.address_of(ident) {
+                    Some(address) => Ok(Expr::Constant(address)),
+                    None => Err(format!("Unknown register, flag or label '{}'", ident)),
+                },
+            },
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parse_number_test() {
+        assert_eq!(parse_number::<u16>("42"), Ok(42));
+        assert_eq!(parse_number::<u16>("0x1F"), Ok(0x1f));
+        assert_eq!(parse_number::<u16>("0b101"), Ok(5));
+        assert!(parse_number::<u16>("0x10000").is_err());
+        assert_eq!(parse_number::<u32>("0x10000"), Ok(0x10000));
+        assert!(parse_number::<u16>("12ab").is_err());
+    }
+
+    #[test]
+    fn parse_test() {
+        assert_eq!(
+            Expr::parse("reg3 == 0x10 && carry"),
+            Ok(Expr::Binary(
+                BinaryOperator::And,
+                Box::new(Expr::Binary(
+                    BinaryOperator::Equal,
+                    Box::new(Expr::Register(3)),
+                    Box::new(Expr::Constant(0x10))
+                )),
+                Box::new(Expr::Flag(Flag::Carry))
+            ))
+        );
+
+        assert!(Expr::parse("").is_err());
+        assert!(Expr::parse("reg8 == 1").is_err());
+        assert!(Expr::parse("reg1 ==").is_err());
+        assert!(Expr::parse("(reg1 == 2").is_err());
+        assert!(Expr::parse("ram[0x10").is_err());
+        assert!(Expr::parse("reg1 $ 2").is_err());
+    }
+
+    #[test]
+    fn evaluate_test() {
+        let mut cpu = CpuState::default();
+        cpu.registers[3] = 0x10;
+        cpu.registers[2] = 0x20;
+        cpu.ram[0x20] = 7;
+        cpu.flags.carry = true;
+
+        let eval = |text: &str, cpu: &CpuState| Expr::parse(text).unwrap().evaluate(cpu);
+
+        assert_eq!(eval("reg3 == 0x10 && carry", &cpu), 1);
+        assert_eq!(eval("reg3 == 0x10 && zero", &cpu), 0);
+        assert_eq!(eval("reg3 != 0x10 || !zero", &cpu), 1);
+        assert_eq!(eval("ram[reg2]", &cpu), 7);
+        assert_eq!(eval("ram[0x8000]", &cpu), 0);
+        assert_eq!(eval("reg2 > reg3 == 1", &cpu), 1);
+        assert_eq!(eval("!(reg3 <= 0x0f)", &cpu), 1);
+        assert_eq!(eval("pc", &cpu), 0);
+
+        // Arithmetic and bit operations
+        assert_eq!(eval("[reg2] * 2 + 1", &cpu), 15);
+        assert_eq!(eval("[reg3 + 0x10]", &cpu), 7);
+        assert_eq!(eval("reg3 & 0x18 == 0x10", &cpu), 1);
+        assert_eq!(eval("1 << 4 | 0b11 ^ 1", &cpu), 0x12);
+        assert_eq!(eval("-1", &cpu), 0xffff);
+        assert_eq!(eval("~reg3 >> 12", &cpu), 0xf);
+        assert_eq!(eval("0 - reg3 / 3 % 4", &cpu), 0xffff);
+        assert_eq!(eval("reg2 / 0", &cpu), 0);
+        assert_eq!(eval("1 << 16", &cpu), 0);
+    }
+
+    #[test]
+    fn symbols_test() {
+        let mut cpu = CpuState::default();
+        cpu.registers[1] = 2;
+        let symbols = SymbolTable::parse("buffer 0x100\n.loop.end 0x7").unwrap();
+
+        assert_eq!(evaluate("buffer + reg1 * 2", &cpu, &symbols), Ok(0x104));
+        assert_eq!(evaluate(".loop.end", &cpu, &symbols), Ok(7));
+        assert!(evaluate("buffr", &cpu, &symbols).is_err());
+
+        assert_eq!(evaluate_constant("buffer + 0x10", &symbols), Ok(0x110));
+        assert!(evaluate_constant("buffer + reg1", &symbols).is_err());
+        assert_eq!(
+            parse_range_with("buffer..buffer+3", &symbols),
+            Ok((0x100, 0x103))
+        );
+        assert_eq!(parse_range("0x10 .. 0x1f"), Ok((0x10, 0x1f)));
+        assert!(parse_range("0x1f..0x10").is_err());
+    }
+}
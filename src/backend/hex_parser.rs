@@ -1,6 +1,9 @@
 use hex::FromHex;
+
+use super::decoder::InstructionWord;
 use std::fs;
 use std::io;
+use std::io::Write;
 use std::vec::Vec;
 
 pub fn bytevec_from_hexfile(file_path: String) -> io::Result<Vec<[u8; 3]>> {
@@ -22,3 +25,40 @@ pub fn bytevec_from_hexfile(file_path: String) -> io::Result<Vec<[u8; 3]>> {
 
     Ok(bytevec)
 }
+
+// Write instruction words in the format produced by masm, 8 words per line
+pub fn write_hexfile(file_path: String, words: &[InstructionWord]) -> io::Result<()> {
+    let mut file = fs::File::create(file_path)?;
+    writeln!(file, "v3.0 hex words plain")?;
+
+    for line in words.chunks(8) {
+        let line: Vec<String> = line.iter().map(|iw| format!("{:05x}", iw.buffer)).collect();
+        writeln!(file, "{}", line.join(" "))?;
+    }
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn write_read_test() {
+        let words: Vec<InstructionWord> = (0..10u32)
+            .map(|i| InstructionWord::from(0x800a1 + i))
+            .collect();
+        let path = std::env::temp_dir().join("mpmp_emu_write_read_test.hex");
+        let path = String::from(path.to_str().unwrap());
+
+        write_hexfile(path.clone(), &words).unwrap();
+        let text = fs::read_to_string(&path).unwrap();
+        assert!(text.starts_with("v3.0 hex words plain\n800a1 800a2 "));
+        assert_eq!(text.lines().count(), 3);
+
+        let bytes = bytevec_from_hexfile(path.clone()).unwrap();
+        let _ = fs::remove_file(path);
+        assert_eq!(bytes.len(), 10);
+        assert_eq!(InstructionWord::from(bytes[9]).buffer, 0x800aa);
+    }
+}
@@ -0,0 +1,122 @@
+use super::expression::parse_number;
+use super::runtime::IoEvent;
+
+// Break conditions tied to the memory mapped IO devices
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+pub enum IoBreak {
+    TerminalWrite(Option<char>), // Any character or only the given one
+    TerminalClear,
+    EmptyInputRead,
+    Rng, // Any access to the RNG
+}
+
+impl IoBreak {
+    // Parse an IO break from a specification of the form
+    // `write [<char>]`, `clear`, `empty` or `rng`. The character may be given
+    // literally, as `\n` or as a number, e.g. `write A`, `write 0x41`.
+    pub fn parse(spec: &str) -> Result<IoBreak, String> {
+        let spec = spec.trim();
+        let (kind, arg) = match spec.split_once(char::is_whitespace) {
+            Some((kind, arg)) => (kind, Some(arg.trim())),
+            None => (spec, None),
+        };
+
+        match (kind, arg) {
+            ("write", None) => Ok(IoBreak::TerminalWrite(None)),
+            ("write", Some(arg)) => Ok(IoBreak::TerminalWrite(Some(parse_char(arg)?))),
+            ("clear", None) => Ok(IoBreak::TerminalClear),
+            ("empty", None) => Ok(IoBreak::EmptyInputRead),
+            ("rng", None) => Ok(IoBreak::Rng),
+            ("clear" | "empty" | "rng", Some(arg)) => Err(format!("Unexpected '{}'", arg)),
+            _ => Err(format!("Unknown IO event '{}'", kind)),
+        }
+    }
+
+    pub fn matches(&self, event: &IoEvent) -> bool {
+        match (self, event) {
+            (IoBreak::TerminalWrite(None), IoEvent::TerminalWrite(_)) => true,
+            (IoBreak::TerminalWrite(Some(expected)), IoEvent::TerminalWrite(ch)) => expected == ch,
+            (IoBreak::TerminalClear, IoEvent::TerminalClear) => true,
+            (IoBreak::EmptyInputRead, IoEvent::InputRead('\0')) => true,
+            (IoBreak::Rng, IoEvent::RngReset | IoEvent::RngAdvance | IoEvent::RngRead) => true,
+            _ => false,
+        }
+    }
+
+    pub fn describe(&self) -> String {
+        match self {
+            IoBreak::TerminalWrite(None) => String::from("terminal write"),
+            IoBreak::TerminalWrite(Some(ch)) => format!("terminal write of {:?}", ch),
+            IoBreak::TerminalClear => String::from("terminal clear"),
+            IoBreak::EmptyInputRead => String::from("read from empty input"),
+            IoBreak::Rng => String::from("RNG access"),
+        }
+    }
+}
+
+pub fn describe_event(event: &IoEvent) -> String {
+    match event {
+        IoEvent::TerminalWrite(ch) => format!("wrote {:?} to terminal", ch),
+        IoEvent::TerminalClear => String::from("cleared terminal"),
+        IoEvent::InputRead('\0') => String::from("read from empty input"),
+        IoEvent::InputRead(ch) => format!("read {:?} from input", ch),
+        IoEvent::InputClear => String::from("cleared input"),
+        IoEvent::RngReset => String::from("reset RNG"),
+        IoEvent::RngAdvance => String::from("advanced RNG"),
+        IoEvent::RngRead => String::from("read RNG"),
+    }
+}
+
+fn parse_char(text: &str) -> Result<char, String> {
+    let mut chars = text.chars();
+    match (chars.next(), chars.next()) {
+        (Some(ch), None) => Ok(ch),
+        _ if text == "\\n" => Ok('\n'),
+        _ if text == "\\t" => Ok('\t'),
+        _ => {
+            let code: u16 = parse_number(text)?;
+            char::from_u32(code as u32).ok_or(format!("Invalid character code '{}'", text))
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parse_test() {
+        assert_eq!(IoBreak::parse("write"), Ok(IoBreak::TerminalWrite(None)));
+        assert_eq!(
+            IoBreak::parse("write A"),
+            Ok(IoBreak::TerminalWrite(Some('A')))
+        );
+        assert_eq!(
+            IoBreak::parse("write 0x41"),
+            Ok(IoBreak::TerminalWrite(Some('A')))
+        );
+        assert_eq!(
+            IoBreak::parse("write \\n"),
+            Ok(IoBreak::TerminalWrite(Some('\n')))
+        );
+        assert_eq!(IoBreak::parse(" clear "), Ok(IoBreak::TerminalClear));
+        assert_eq!(IoBreak::parse("empty"), Ok(IoBreak::EmptyInputRead));
+        assert_eq!(IoBreak::parse("rng"), Ok(IoBreak::Rng));
+
+        assert!(IoBreak::parse("").is_err());
+        assert!(IoBreak::parse("joystick").is_err());
+        assert!(IoBreak::parse("clear now").is_err());
+        assert!(IoBreak::parse("write AB").is_err());
+    }
+
+    #[test]
+    fn matches_test() {
+        assert!(IoBreak::TerminalWrite(None).matches(&IoEvent::TerminalWrite('x')));
+        assert!(IoBreak::TerminalWrite(Some('x')).matches(&IoEvent::TerminalWrite('x')));
+        assert!(!IoBreak::TerminalWrite(Some('y')).matches(&IoEvent::TerminalWrite('x')));
+        assert!(IoBreak::EmptyInputRead.matches(&IoEvent::InputRead('\0')));
+        assert!(!IoBreak::EmptyInputRead.matches(&IoEvent::InputRead('a')));
+        assert!(IoBreak::Rng.matches(&IoEvent::RngAdvance));
+        assert!(!IoBreak::TerminalClear.matches(&IoEvent::InputClear));
+    }
+}
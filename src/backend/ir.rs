@@ -24,7 +24,7 @@ pub struct TernaryOp {
     pub source_c: Register,
 }
 
-#[derive(Debug, PartialEq, Eq)]
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
 pub enum JumpCondition {
     Always,
     Zero,
@@ -49,6 +49,7 @@ pub enum LoadSource {
 pub enum Operation {
     Halt,
     Noop,
+    Debug, // Software breakpoint
 
     Inc(UnaryOp),
     Dec(UnaryOp),
@@ -88,12 +89,26 @@ pub enum Operation {
     },
 }
 
+impl Operation {
+    // The address a relative jump located at the given address lands on when taken.
+    // Note that the program counter is incremented after the offset has been added.
+    pub fn relative_jump_target(&self, address: u16) -> Option<u16> {
+        match self {
+            Operation::Jump {
+                target: JumpTarget::AddressOffsetConstant(offset),
+                ..
+            } => Some(address.wrapping_add_signed(*offset).wrapping_add(1)),
+            _ => None,
+        }
+    }
+}
+
 // Functionalities for disassembling instructions (untested!)
 use Operation::*;
 impl Operation {
     pub fn get_assembly_string(&self) -> String {
         match self {
-            Noop | Halt => String::from(self.get_assembly_opname()),
+            Noop | Halt | Debug => String::from(self.get_assembly_opname()),
             Inc(unop) | Dec(unop) => {
                 format!("{} %reg{}", self.get_assembly_opname(), unop.source_a)
             }
@@ -105,8 +120,10 @@ impl Operation {
                     unop.source_a
                 )
             }
+            // tst only compares and has no target
+            Test(o) => format!("tst %reg{} %reg{}", o.source_a, o.source_b),
             Add(o) | AddCarry(o) | Sub(o) | SubCarry(o) | And(o) | Or(o) | Xor(o) | Xnor(o)
-            | ShiftLeft(o) | ShiftRight(o) | Multiply(o) | Test(o) => {
+            | ShiftLeft(o) | ShiftRight(o) | Multiply(o) => {
                 format!(
                     "{} %reg{} %reg{} %reg{}",
                     self.get_assembly_opname(),
@@ -125,8 +142,9 @@ impl Operation {
                 JumpTarget::AbsoluteAdressRegister(reg) => {
                     format!("{} %reg{}", self.get_assembly_opname(), *reg)
                 }
+                // Written relative to the jump itself like in masm sources, see relative_jump_target
                 JumpTarget::AddressOffsetConstant(offset) => {
-                    format!("{} {}", self.get_assembly_opname(), *offset)
+                    format!("{} {}", self.get_assembly_opname(), *offset as i32 + 1)
                 }
             },
             Load {
@@ -164,10 +182,11 @@ impl Operation {
         }
     }
 
-    fn get_assembly_opname(&self) -> &str {
+    pub fn get_assembly_opname(&self) -> &str {
         match self {
             Self::Noop => "nop",
             Self::Halt => "hlt",
+            Self::Debug => "dbg",
             Self::Add(_) => "add",
             Self::AddCarry(_) => "addc",
             Self::Add3(_) => "add3",
@@ -1,5 +1,17 @@
+pub mod annotations;
+pub mod assembler;
+pub mod breakpoints;
+pub mod changes;
+pub mod debugger;
 pub mod decoder;
+pub mod expression;
 pub mod hex_parser;
+pub mod io_breaks;
 pub mod ir;
 pub mod program;
 pub mod runtime;
+pub mod search;
+pub mod source_map;
+pub mod symbols;
+pub mod watch_list;
+pub mod watchpoints;
@@ -1,22 +1,139 @@
+use std::fs;
+use std::path::Path;
+
+use super::assembler;
 use super::decoder::InstructionWord;
+use super::hex_parser;
 use super::ir::*;
+use super::runtime::CpuState;
+use super::source_map::SourceMap;
+use super::symbols::SymbolTable;
 
 pub struct Program {
     pub operations: Vec<Operation>,
-    pub breakpoints: Vec<bool>,
     pub instruction_words: Vec<InstructionWord>,
+    pub symbols: SymbolTable,
+    pub source: Option<SourceMap>, // Only for programs assembled from a .s file
 }
 
 impl Default for Program {
     fn default() -> Self {
         Program {
             operations: Vec::new(),
-            breakpoints: Vec::new(),
             instruction_words: Vec::new(),
+            symbols: SymbolTable::default(),
+            source: None,
         }
     }
 }
 
+impl Program {
+    // Load a program from a .hex file or assemble it from a .s file. Symbols
+    // for .hex files are taken from a .sym file next to it, if there is one.
+    pub fn load(path: &str) -> Result<Program, String> {
+        if path.ends_with(".s") {
+            let source = fs::read_to_string(path).map_err(|err| format!("{}: {}", path, err))?;
+            return assembler::assemble_source(&source).map_err(|msg| format!("{}:{}", path, msg));
+        }
+
+        let bytes = hex_parser::bytevec_from_hexfile(String::from(path))
+            .map_err(|err| format!("{}: {}", path, err))?;
+        let mut program = Program::from(bytes.as_slice());
+
+        let symbol_path = Path::new(path).with_extension("sym");
+        if symbol_path.exists() {
+            program.load_symbols(&symbol_path.to_string_lossy())?;
+        }
+
+        Ok(program)
+    }
+
+    // Replace the symbols with the ones from a symbol file
+    pub fn load_symbols(&mut self, path: &str) -> Result<(), String> {
+        let text = fs::read_to_string(path).map_err(|err| format!("{}: {}", path, err))?;
+        self.symbols = SymbolTable::parse(&text).map_err(|msg| format!("{}:{}", path, msg))?;
+        Ok(())
+    }
+
+    // Absolute target of the jump at the address. Register jumps can only be
+    // resolved at the program counter, elsewhere the register value is unknown.
+    // Like every instruction, they increment the program counter after setting it,
+    // so execution continues after the address in the register.
+    pub fn jump_target(&self, address: u16, cpu: &CpuState) -> Option<u16> {
+        match self.operations.get(address as usize)? {
+            Operation::Jump {
+                target: JumpTarget::AbsoluteAdressRegister(reg),
+                ..
+            } if address == cpu.pcounter => Some(cpu.registers[*reg].wrapping_add(1)),
+            op => op.relative_jump_target(address),
+        }
+    }
+
+    // Disassemble the instruction at the address. Jumps are annotated with their
+    // target, e.g. `jnzr loop (0x0003)` or `jmp %reg2 (end 0x000b)`.
+    pub fn assembly_string(&self, address: u16, cpu: &CpuState) -> String {
+        let op = &self.operations[address as usize];
+        let Some(target) = self.jump_target(address, cpu) else {
+            return op.get_assembly_string();
+        };
+
+        match (
+            op.relative_jump_target(address),
+            self.symbols.label_at(target),
+        ) {
+            (Some(_), Some(label)) => {
+                format!("{} {} ({:#06x})", op.get_assembly_opname(), label, target)
+            }
+            (None, Some(label)) => {
+                format!("{} ({} {:#06x})", op.get_assembly_string(), label, target)
+            }
+            (_, None) => format!("{} ({:#06x})", op.get_assembly_string(), target),
+        }
+    }
+
+    // Find the address in a new version of the program. Addresses are kept
+    // relative to the closest label before them if the new version still has
+    // that label, otherwise they are kept as they are.
+    pub fn relocate(&self, address: u16, new_program: &Program) -> u16 {
+        self.symbols
+            .nearest_label(address)
+            .and_then(|(label, label_address)| {
+                let new_label_address = new_program.symbols.address_of(label)?;
+                Some(new_label_address.wrapping_add(address - label_address))
+            })
+            .unwrap_or(address)
+    }
+
+    // Replace the instruction at the given address
+    pub fn patch(&mut self, address: u16, word: InstructionWord) -> Result<(), String> {
+        match self.instruction_words.get_mut(address as usize) {
+            Some(old_word) => {
+                *old_word = word;
+                self.operations[address as usize] = Operation::from(word);
+                Ok(())
+            }
+            None => Err(format!("Address {:#X} is outside of the program", address)),
+        }
+    }
+
+    // Find the innermost loop containing the address, that is the closest backward
+    // relative jump at or after the address landing at or before it. Returns the
+    // first and last address of the loop body.
+    pub fn enclosing_loop(&self, address: u16) -> Option<(u16, u16)> {
+        self.operations
+            .iter()
+            .enumerate()
+            .skip(address as usize)
+            .find_map(|(jump_address, op)| {
+                let jump_address = jump_address as u16;
+                match op.relative_jump_target(jump_address) {
+                    Some(target) if target <= address => Some((target, jump_address)),
+                    _ => None,
+                }
+            })
+    }
+}
+
 impl From<&[u32]> for Program {
     fn from(coll: &[u32]) -> Self {
         let mut op_vec = Vec::new();
@@ -28,13 +145,11 @@ impl From<&[u32]> for Program {
             op_vec.push(Operation::from(instr))
         }
 
-        let mut brk_vec = Vec::new();
-        brk_vec.resize(op_vec.len(), false);
-
         Program {
             operations: op_vec,
-            breakpoints: brk_vec,
             instruction_words: instr_vec,
+            symbols: SymbolTable::default(),
+            source: None,
         }
     }
 }
@@ -50,13 +165,94 @@ impl From<&[[u8; 3]]> for Program {
             op_vec.push(Operation::from(instr));
         }
 
-        let mut brk_vec = Vec::new();
-        brk_vec.resize(op_vec.len(), false);
-
         Program {
             operations: op_vec,
-            breakpoints: brk_vec,
             instruction_words: instr_vec,
+            symbols: SymbolTable::default(),
+            source: None,
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /*
+    main:
+      ldc %reg0 1
+      ldc %reg1 1
+      ldc %reg2 3
+    loop:
+      add %reg0 %reg0 %reg1
+      mov %reg5 %reg0
+      dec %reg2
+      jzr end
+      add %reg1 %reg0 %reg1
+      mov %reg5 %reg1
+      dec %reg2
+      jnzr loop
+    end:
+      hlt
+    */
+    const PMEM: [u32; 12] = [
+        0x00081u32, 0x00091u32, 0x000a3u32, 0x00800u32, 0xa0048u32, 0x00206u32, 0x00459u32,
+        0x20800u32, 0xa0148u32, 0x00206u32, 0xff85au32, 0x0007fu32,
+    ];
+
+    #[test]
+    fn enclosing_loop_test() {
+        let program = Program::from(PMEM.as_slice());
+
+        assert_eq!(program.enclosing_loop(0x2), None);
+        assert_eq!(program.enclosing_loop(0x3), Some((0x3, 0xa)));
+        assert_eq!(program.enclosing_loop(0x6), Some((0x3, 0xa)));
+        assert_eq!(program.enclosing_loop(0xa), Some((0x3, 0xa)));
+        assert_eq!(program.enclosing_loop(0xb), None);
+    }
+
+    #[test]
+    fn relocate_test() {
+        let old =
+            assembler::assemble_source("main: nop\nloop: inc %reg0\n jr loop\nend: hlt").unwrap();
+        let new = assembler::assemble_source("main: nop\nnop\nloop: inc %reg0\n jr loop\nend: hlt")
+            .unwrap();
+
+        assert_eq!(old.relocate(0x0, &new), 0x0);
+        assert_eq!(old.relocate(0x1, &new), 0x2);
+        assert_eq!(old.relocate(0x2, &new), 0x3);
+        assert_eq!(old.relocate(0x3, &new), 0x4);
+
+        // Without labels addresses stay where they are
+        let plain = Program::from(PMEM.as_slice());
+        assert_eq!(plain.relocate(0x5, &new), 0x5);
+        assert_eq!(old.relocate(0x3, &plain), 0x3);
+    }
+
+    #[test]
+    fn assembly_string_test() {
+        let mut program = Program::from(PMEM.as_slice());
+        let mut cpu = CpuState::default();
+        assert_eq!(program.assembly_string(0xa, &cpu), "jnzr -7 (0x0003)");
+        assert_eq!(program.assembly_string(0x4, &cpu), "mov %reg5 %reg0");
+
+        program.symbols.insert("loop", 0x3).unwrap();
+        assert_eq!(program.assembly_string(0xa, &cpu), "jnzr loop (0x0003)");
+
+        // Register jumps are only resolved when they are about to be executed
+        let word = assembler::parse_word("jmp %reg2").unwrap();
+        program.patch(0x4, word).unwrap();
+        cpu.registers[2] = 0x2;
+        assert_eq!(program.jump_target(0x4, &cpu), None);
+        assert_eq!(program.assembly_string(0x4, &cpu), "jmp %reg2");
+        cpu.pcounter = 0x4;
+        // The jump continues at reg2 + 1, like the runtime does
+        assert_eq!(program.jump_target(0x4, &cpu), Some(0x3));
+        assert_eq!(
+            program.assembly_string(0x4, &cpu),
+            "jmp %reg2 (loop 0x0003)"
+        );
+        cpu.execute_operation(&program.operations[0x4]);
+        assert_eq!(cpu.pcounter, 0x3);
+    }
+}
@@ -1,7 +1,9 @@
+use std::collections::VecDeque;
+
 use super::ir::*;
 use super::program::Program;
 
-#[derive(Debug, PartialEq, Eq)]
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
 pub struct Flags {
     pub carry: bool,
     pub overflow: bool,
@@ -43,6 +45,112 @@ impl IOStream {
     }
 }
 
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+pub enum AccessKind {
+    Read,
+    Write,
+}
+
+// A single access to RAM performed by a load or store
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+pub struct MemoryAccess {
+    pub kind: AccessKind,
+    pub address: u16,
+    pub value: u16,
+}
+
+// An interaction with one of the memory mapped IO devices
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+pub enum IoEvent {
+    TerminalWrite(char), // 0x8000
+    TerminalClear,       // 0x8001
+    InputRead(char),     // 0x8002, reads '\0' if the input stream is empty
+    InputClear,          // 0x8003
+    RngReset,            // 0x8005
+    RngAdvance,          // 0x8006
+    RngRead,             // 0x8007
+}
+
+// Number of taken jumps kept in the branch history
+pub const BRANCH_HISTORY_SIZE: usize = 64;
+
+// A taken jump, the flags are the ones the condition was checked against
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+pub struct Branch {
+    pub source: u16,
+    pub target: u16,
+    pub condition: JumpCondition,
+    pub flags: Flags,
+    pub step: u64, // Value of CpuState::executed when the jump was executed
+}
+
+impl Branch {
+    pub fn condition_name(&self) -> &'static str {
+        match self.condition {
+            JumpCondition::Always => "always",
+            JumpCondition::Zero => "zero",
+            JumpCondition::NotZero => "not zero",
+            JumpCondition::Carry => "carry",
+            JumpCondition::NotCarry => "not carry",
+        }
+    }
+
+    // Flags as letters, upper case if set, e.g. `zCo`
+    pub fn flag_letters(&self) -> String {
+        [
+            (self.flags.zero, 'z'),
+            (self.flags.carry, 'c'),
+            (self.flags.overflow, 'o'),
+        ]
+        .iter()
+        .map(|(set, letter)| match set {
+            true => letter.to_ascii_uppercase(),
+            false => *letter,
+        })
+        .collect()
+    }
+
+    pub fn describe(&self) -> String {
+        format!(
+            "{:#06x} -> {:#06x} ({}, flags {}) at step {}",
+            self.source,
+            self.target,
+            self.condition_name(),
+            self.flag_letters(),
+            self.step
+        )
+    }
+}
+
+// The most recently taken jumps, the ISA has no call stack to tell how the
+// program got somewhere
+#[derive(Debug, PartialEq, Eq, Default)]
+pub struct BranchHistory {
+    branches: VecDeque<Branch>,
+}
+
+impl BranchHistory {
+    fn push(&mut self, branch: Branch) {
+        if self.branches.len() == BRANCH_HISTORY_SIZE {
+            self.branches.pop_front();
+        }
+        self.branches.push_back(branch);
+    }
+
+    // The most recent jump first
+    pub fn iter(&self) -> impl Iterator<Item = &Branch> {
+        self.branches.iter().rev()
+    }
+
+    pub fn len(&self) -> usize {
+        self.branches.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.branches.is_empty()
+    }
+}
+
 #[derive(Debug, PartialEq, Eq)]
 pub struct CpuState {
     pub registers: Registers,
@@ -51,6 +159,13 @@ pub struct CpuState {
     pub pmem: Pmem,
     pub pcounter: u16,
     pub received_halt: bool,
+    pub executed: u64, // Number of instructions executed since the last reset
+
+    // The RAM access performed by the last executed operation, if any
+    pub last_access: Option<MemoryAccess>,
+    // The IO event caused by the last executed operation, if any
+    pub last_io_event: Option<IoEvent>,
+    pub branches: BranchHistory,
 
     pub istream: IOStream,
     pub ostream: IOStream,
@@ -70,7 +185,11 @@ impl Default for CpuState {
             ram: [0u16; RAM_SIZE],
             pmem: [0u32; PMEM_SIZE],
             received_halt: false,
+            executed: 0,
             pcounter: 0,
+            last_access: None,
+            last_io_event: None,
+            branches: BranchHistory::default(),
 
             istream: IOStream {
                 string: String::new(),
@@ -90,14 +209,95 @@ impl CpuState {
         self.flags.zero = res == 0;
     }
 
+    fn check_ram_range(start: u16, end: u16) -> Result<(), String> {
+        match (end as usize) < RAM_SIZE && start <= end {
+            true => Ok(()),
+            false => Err(format!(
+                "Range {:#06x}..{:#06x} is outside of RAM",
+                start, end
+            )),
+        }
+    }
+
+    // Set all words in the inclusive range to value
+    pub fn fill_ram(&mut self, start: u16, end: u16, value: u16) -> Result<(), String> {
+        Self::check_ram_range(start, end)?;
+        self.ram[start as usize..=end as usize].fill(value);
+        Ok(())
+    }
+
+    // Copy the inclusive range to dest, the ranges may overlap
+    pub fn copy_ram(&mut self, start: u16, end: u16, dest: u16) -> Result<(), String> {
+        Self::check_ram_range(start, end)?;
+        let dest_end = dest
+            .checked_add(end - start)
+            .ok_or(format!("Destination {:#06x} is outside of RAM", dest))?;
+        Self::check_ram_range(dest, dest_end)?;
+        self.ram
+            .copy_within(start as usize..=end as usize, dest as usize);
+        Ok(())
+    }
+
+    // Store one character per word, followed by a terminating 0.
+    // Returns the number of words written.
+    pub fn write_ram_string(&mut self, address: u16, text: &str) -> Result<u16, String> {
+        let words: Vec<u16> = text
+            .chars()
+            .map(|ch| ch as u16)
+            .chain(std::iter::once(0))
+            .collect();
+        let end = address as usize + words.len() - 1;
+        if end >= RAM_SIZE {
+            return Err(format!("String does not fit into RAM at {:#06x}", address));
+        }
+
+        self.ram[address as usize..=end].copy_from_slice(&words);
+        Ok(words.len() as u16)
+    }
+
+    // Human readable overview of program counter, flags and registers
+    pub fn summary(&self) -> String {
+        let registers: Vec<String> = self
+            .registers
+            .iter()
+            .enumerate()
+            .map(|(i, value)| format!("reg{}={:#06x}", i, value))
+            .collect();
+
+        format!(
+            "pc={:#06x} zero={} carry={} overflow={}\n{}",
+            self.pcounter,
+            self.flags.zero,
+            self.flags.carry,
+            self.flags.overflow,
+            registers.join(" ")
+        )
+    }
+
     pub fn execute_next_prog_op(&mut self, prog: &Program) {
+        self.executed += 1;
         self.execute_operation(&prog.operations[self.pcounter as usize])
     }
 
+    fn condition_holds(&self, condition: JumpCondition) -> bool {
+        match condition {
+            JumpCondition::Always => true,
+            JumpCondition::Zero => self.flags.zero,
+            JumpCondition::NotZero => !self.flags.zero,
+            JumpCondition::Carry => self.flags.carry,
+            JumpCondition::NotCarry => !self.flags.carry,
+        }
+    }
+
     pub fn execute_operation(&mut self, op: &Operation) {
+        self.last_access = None;
+        self.last_io_event = None;
+        let source = self.pcounter;
+
         match op {
             Operation::Halt => self.received_halt = true,
-            Operation::Noop => {}
+            // The debugger decides what to do about these
+            Operation::Noop | Operation::Debug => {}
             Operation::Inc(op) => {
                 let res = self.registers[op.source_a].overflowing_add(1);
                 self.registers[op.source_a] = res.0;
@@ -221,20 +421,30 @@ impl CpuState {
                 match address {
                     // Read a character from input stream
                     0x8002 => {
-                        self.registers[*target_register] = self.istream.consume_first() as u16
+                        let ch = self.istream.consume_first();
+                        self.registers[*target_register] = ch as u16;
+                        self.last_io_event = Some(IoEvent::InputRead(ch));
                     }
                     // Read the state of the joystick. Not implemented
                     0x8004 => {
                         self.registers[*target_register] = 0x0u16;
                     }
                     // Read RNG state
-                    0x8007 => self.registers[*target_register] = self.rng_state,
+                    0x8007 => {
+                        self.registers[*target_register] = self.rng_state;
+                        self.last_io_event = Some(IoEvent::RngRead);
+                    }
                     // Else perform default load from RAM
                     _ => {
                         self.registers[*target_register] = match address < 0x8000 {
                             true => self.ram[address as usize],
                             false => 0,
-                        }
+                        };
+                        self.last_access = Some(MemoryAccess {
+                            kind: AccessKind::Read,
+                            address,
+                            value: self.registers[*target_register],
+                        });
                     }
                 }
             }
@@ -258,23 +468,37 @@ impl CpuState {
                         let ch: char =
                             std::char::from_u32(self.registers[*data_register as usize] as u32)
                                 .unwrap();
-                        self.ostream.append_char(ch)
+                        self.ostream.append_char(ch);
+                        self.last_io_event = Some(IoEvent::TerminalWrite(ch));
                     }
                     // Clear ostream
                     0x8001 => {
                         self.ostream.clear();
+                        self.last_io_event = Some(IoEvent::TerminalClear);
                     }
                     // Clear istream
                     0x8003 => {
                         self.istream.clear();
+                        self.last_io_event = Some(IoEvent::InputClear);
                     }
                     // Reset RNG
-                    0x8005 => self.rng_state = rand::random(),
+                    0x8005 => {
+                        self.rng_state = rand::random();
+                        self.last_io_event = Some(IoEvent::RngReset);
+                    }
                     // Enter next RNG state
-                    0x8006 => self.rng_state = rand::random(),
+                    0x8006 => {
+                        self.rng_state = rand::random();
+                        self.last_io_event = Some(IoEvent::RngAdvance);
+                    }
                     // Else perform default store to ram
                     _ => {
                         self.ram[address as usize] = self.registers[*data_register];
+                        self.last_access = Some(MemoryAccess {
+                            kind: AccessKind::Write,
+                            address,
+                            value: self.registers[*data_register],
+                        });
                     }
                 }
             }
@@ -341,13 +565,27 @@ impl CpuState {
         // There are cases in which the pcounter overflows, (mostly when jumping to address 0)
         // We just wrap and it should be fine
         self.pcounter = self.pcounter.wrapping_add(1);
+
+        // Jumps leave the flags alone, so the condition can be checked afterwards
+        if let Operation::Jump { condition, .. } = op {
+            if self.condition_holds(*condition) {
+                self.branches.push(Branch {
+                    source,
+                    target: self.pcounter,
+                    condition: *condition,
+                    flags: self.flags,
+                    step: self.executed,
+                });
+            }
+        }
     }
 }
 
 #[cfg(test)]
 mod tests {
+    use super::super::ir::JumpCondition;
     use super::super::program::Program;
-    use super::CpuState;
+    use super::{Branch, CpuState, BRANCH_HISTORY_SIZE};
 
     /*
     ldc %reg0 0x5
@@ -756,4 +994,53 @@ mod tests {
 
         assert_eq!(cpu.ostream.string, String::from("Hello world!"));
     }
+
+    #[test]
+    fn branch_history_test() {
+        let mut cpu = CpuState::default();
+        let program_abc = Program::from(PMEM4.as_slice());
+
+        while !cpu.received_halt {
+            cpu.execute_next_prog_op(&program_abc)
+        }
+
+        // The loop jumps back after every character but the last one
+        assert_eq!(cpu.branches.len(), 25);
+        let latest = cpu.branches.iter().next().unwrap();
+        assert_eq!((latest.source, latest.target), (0x8, 0x5));
+        assert_eq!(latest.condition, JumpCondition::NotZero);
+        assert!(!latest.flags.zero && latest.flags.carry); // tst of 0x5a and 0x5b
+        assert_eq!(latest.flag_letters(), "zCo");
+        assert_eq!(latest.step, cpu.executed - 5); // Last loop iteration and hlt
+
+        // Only the most recent jumps are kept
+        let branch = Branch { step: 0, ..*latest };
+        for _ in 0..BRANCH_HISTORY_SIZE {
+            cpu.branches.push(branch);
+        }
+        assert_eq!(cpu.branches.len(), BRANCH_HISTORY_SIZE);
+        assert!(cpu.branches.iter().all(|b| b.step == 0));
+    }
+
+    #[test]
+    fn ram_editing_test() {
+        let mut cpu = CpuState::default();
+
+        cpu.fill_ram(0x10, 0x13, 0xab).unwrap();
+        assert_eq!(cpu.ram[0x0f..0x15], [0x0, 0xab, 0xab, 0xab, 0xab, 0x0]);
+
+        cpu.copy_ram(0x10, 0x13, 0x12).unwrap();
+        assert_eq!(
+            cpu.ram[0x10..0x17],
+            [0xab, 0xab, 0xab, 0xab, 0xab, 0xab, 0x0]
+        );
+
+        assert_eq!(cpu.write_ram_string(0x20, "hi"), Ok(3));
+        assert_eq!(cpu.ram[0x20..0x24], [0x68, 0x69, 0x0, 0x0]);
+
+        assert!(cpu.fill_ram(0x7ffe, 0x8000, 0).is_err());
+        assert!(cpu.copy_ram(0x0, 0x10, 0x7ff8).is_err());
+        assert!(cpu.copy_ram(0x0, 0x10, 0xfff8).is_err());
+        assert!(cpu.write_ram_string(0x7fff, "a").is_err());
+    }
 }
@@ -0,0 +1,244 @@
+// Searching RAM for values or strings and program memory for instructions, e.g.
+// `ram 0x41, 0x42`, `ram "Hello"`, `program st`, `program add %reg5` or
+// `program 0x0??68` (raw instruction word, ? matches any hex digit).
+
+use super::expression::evaluate_constant;
+use super::ir::Register;
+use super::program::Program;
+use super::runtime::CpuState;
+use super::symbols::SymbolTable;
+
+// Addresses listed by summary()
+const MAX_LISTED: usize = 16;
+
+// All tokens have to match an instruction
+pub struct InstructionPattern {
+    mnemonic: Option<String>,
+    registers: Vec<Register>,
+    word: Option<(u32, u32)>, // Mask of the given digits and their value
+}
+
+pub enum Search {
+    Ram(Vec<u16>), // Sequence of consecutive words
+    Program(InstructionPattern),
+}
+
+fn parse_words(text: &str, symbols: &SymbolTable) -> Result<Vec<u16>, String> {
+    // A string is searched for one character per word
+    if let Some(text) = text.strip_prefix('"') {
+        return match text.strip_suffix('"') {
+            Some("") | None => Err(String::from("Missing closing quote or empty string")),
+            Some(text) => Ok(text.chars().map(|ch| ch as u16).collect()),
+        };
+    }
+    // Values may be expressions containing spaces, so they are separated by commas
+    text.split(',')
+        .map(|value| evaluate_constant(value, symbols))
+        .collect()
+}
+
+// Five hex digits, each of them may be ?
+fn parse_word_pattern(digits: &str) -> Result<(u32, u32), String> {
+    if digits.len() != 5 {
+        return Err(format!(
+            "Instruction words have 5 hex digits, got '{}'",
+            digits
+        ));
+    }
+    let mut mask = 0;
+    let mut value = 0;
+    for ch in digits.chars() {
+        mask <<= 4;
+        value <<= 4;
+        match ch {
+            '?' => {}
+            ch => match ch.to_digit(16) {
+                Some(digit) => {
+                    mask |= 0xF;
+                    value |= digit;
+                }
+                None => return Err(format!("Invalid hex digit '{}'", ch)),
+            },
+        }
+    }
+    Ok((mask, value))
+}
+
+impl InstructionPattern {
+    pub fn parse(text: &str) -> Result<InstructionPattern, String> {
+        let mut pattern = InstructionPattern {
+            mnemonic: None,
+            registers: Vec::new(),
+            word: None,
+        };
+
+        for token in text.split_whitespace() {
+            let lowercase = token.to_lowercase();
+            if let Some(reg) = lowercase.strip_prefix("%reg") {
+                match reg.parse::<Register>() {
+                    Ok(reg) if reg < 8 => pattern.registers.push(reg),
+                    _ => return Err(format!("Unknown register '{}'", token)),
+                }
+            } else if let Some(digits) = lowercase.strip_prefix("0x") {
+                pattern.word = Some(parse_word_pattern(digits)?);
+            } else if pattern.mnemonic.is_some() {
+                return Err(format!(
+                    "Unexpected '{}', only one mnemonic can be given",
+                    token
+                ));
+            } else {
+                pattern.mnemonic = Some(lowercase);
+            }
+        }
+
+        match text.trim().is_empty() {
+            true => Err(String::from("Missing instruction pattern")),
+            false => Ok(pattern),
+        }
+    }
+
+    pub fn matches(&self, program: &Program, address: u16) -> bool {
+        let op = &program.operations[address as usize];
+        let word = program.instruction_words[address as usize].buffer;
+
+        let assembly = op.get_assembly_string();
+        let operands = assembly.split_whitespace().skip(1);
+
+        self.mnemonic
+            .as_ref()
+            .is_none_or(|mnemonic| op.get_assembly_opname() == mnemonic)
+            && self.word.is_none_or(|(mask, value)| word & mask == value)
+            && self.registers.iter().all(|reg| {
+                let name = format!("%reg{}", reg);
+                operands.clone().any(|operand| operand == name)
+            })
+    }
+}
+
+impl Search {
+    // `ram <values>` or `program <pattern>`
+    pub fn parse(text: &str, symbols: &SymbolTable) -> Result<Search, String> {
+        let text = text.trim();
+        let (target, pattern) = text.split_once(char::is_whitespace).unwrap_or((text, ""));
+        let pattern = pattern.trim();
+        match target {
+            _ if pattern.is_empty() => Err(String::from("Missing search pattern")),
+            "ram" => Ok(Search::Ram(parse_words(pattern, symbols)?)),
+            "program" => Ok(Search::Program(InstructionPattern::parse(pattern)?)),
+            target => Err(format!("Cannot search '{}', use ram or program", target)),
+        }
+    }
+
+    // Addresses of all matches in ascending order
+    pub fn matches(&self, cpu: &CpuState, program: &Program) -> Vec<u16> {
+        match self {
+            Search::Ram(words) => cpu
+                .ram
+                .windows(words.len())
+                .enumerate()
+                .filter(|(_, window)| window == words)
+                .map(|(address, _)| address as u16)
+                .collect(),
+            Search::Program(pattern) => (0..program.operations.len() as u16)
+                .filter(|address| pattern.matches(program, *address))
+                .collect(),
+        }
+    }
+
+    // Number of matches and the first addresses, for the message log
+    pub fn summary(&self, cpu: &CpuState, program: &Program) -> String {
+        let matches = self.matches(cpu, program);
+        let addresses: Vec<String> = matches
+            .iter()
+            .take(MAX_LISTED)
+            .map(|address| format!("{:#06x}", address))
+            .collect();
+        match matches.len() {
+            0 => String::from("No matches"),
+            n if n > MAX_LISTED => format!("{} matches: {} ...", n, addresses.join(" ")),
+            n => format!("{} match(es): {}", n, addresses.join(" ")),
+        }
+    }
+
+    // The closest match after (or before) the address, wrapping around at the ends
+    pub fn find(&self, cpu: &CpuState, program: &Program, from: u16, forward: bool) -> Option<u16> {
+        let matches = self.matches(cpu, program);
+        match forward {
+            true => matches
+                .iter()
+                .find(|address| **address > from)
+                .or(matches.first()),
+            false => matches
+                .iter()
+                .rev()
+                .find(|address| **address < from)
+                .or(matches.last()),
+        }
+        .copied()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /*
+    ldc %reg0 0x10
+    ldc %reg1 0x5
+    st %reg0 %reg1
+    */
+    const PMEM: [u32; 3] = [0x00180u32, 0x00095u32, 0x00168u32];
+
+    #[test]
+    fn ram_search_test() {
+        let mut cpu = CpuState::default();
+        let program = Program::default();
+        let symbols = SymbolTable::parse("text 0x20").unwrap();
+        cpu.write_ram_string(0x20, "abab").unwrap();
+        cpu.ram[0x100] = 0x61;
+
+        let search = Search::parse("ram \"ab\"", &symbols).unwrap();
+        assert_eq!(search.matches(&cpu, &program), [0x20, 0x22]);
+        assert_eq!(search.find(&cpu, &program, 0x20, true), Some(0x22));
+        assert_eq!(search.find(&cpu, &program, 0x22, true), Some(0x20));
+        assert_eq!(search.find(&cpu, &program, 0x20, false), Some(0x22));
+        assert_eq!(search.summary(&cpu, &program), "2 match(es): 0x0020 0x0022");
+
+        let search = Search::parse("ram 0x61", &symbols).unwrap();
+        assert_eq!(search.matches(&cpu, &program), [0x20, 0x22, 0x100]);
+        let search = Search::parse("ram 0x62, 0", &symbols).unwrap();
+        assert_eq!(search.matches(&cpu, &program), [0x23]);
+        let search = Search::parse("ram text + 1", &symbols).unwrap();
+        assert!(search.matches(&cpu, &program).is_empty());
+
+        assert!(Search::parse("ram", &symbols).is_err());
+        assert!(Search::parse("ram \"\"", &symbols).is_err());
+        assert!(Search::parse("ram reg1", &symbols).is_err());
+        assert!(Search::parse("stack 0x1", &symbols).is_err());
+    }
+
+    #[test]
+    fn program_search_test() {
+        let cpu = CpuState::default();
+        let program = Program::from(PMEM.as_slice());
+        let symbols = SymbolTable::default();
+        let matches = |pattern: &str| {
+            Search::parse(&format!("program {}", pattern), &symbols)
+                .unwrap()
+                .matches(&cpu, &program)
+        };
+
+        assert_eq!(matches("ldc"), [0, 1]);
+        assert_eq!(matches("ST"), [2]);
+        assert_eq!(matches("%reg1"), [1, 2]);
+        assert_eq!(matches("ldc %reg1"), [1]);
+        assert_eq!(matches("st %reg0 %reg1"), [2]);
+        assert_eq!(matches("0x00??0"), [0]);
+        assert_eq!(matches("0x????8 %reg0"), [2]);
+        assert!(matches("add").is_empty());
+
+        assert!(Search::parse("program %reg8", &symbols).is_err());
+        assert!(Search::parse("program 0x123", &symbols).is_err());
+        assert!(Search::parse("program ld st", &symbols).is_err());
+    }
+}
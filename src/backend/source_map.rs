@@ -0,0 +1,64 @@
+// Mapping between program memory addresses and the lines of the assembly
+// source a program was built from
+#[derive(Default)]
+pub struct SourceMap {
+    lines: Vec<String>,
+    address_lines: Vec<usize>, // Source line of each instruction, counted from 0
+}
+
+impl SourceMap {
+    pub fn new(text: &str, address_lines: Vec<usize>) -> Self {
+        SourceMap {
+            lines: text.lines().map(String::from).collect(),
+            address_lines,
+        }
+    }
+
+    pub fn lines(&self) -> &[String] {
+        &self.lines
+    }
+
+    pub fn instruction_count(&self) -> usize {
+        self.address_lines.len()
+    }
+
+    pub fn line_of(&self, address: u16) -> Option<usize> {
+        self.address_lines.get(address as usize).copied()
+    }
+
+    // Address of the instruction on the line. Lines without an instruction, like
+    // labels and comments, map to the next instruction below.
+    pub fn address_of_line(&self, line: usize) -> Option<u16> {
+        self.address_lines
+            .iter()
+            .position(|l| *l >= line)
+            .map(|address| address as u16)
+    }
+
+    // Address of the instruction written on exactly this line
+    pub fn address_on_line(&self, line: usize) -> Option<u16> {
+        self.address_lines
+            .iter()
+            .position(|l| *l == line)
+            .map(|address| address as u16)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn lookup_test() {
+        let map = SourceMap::new("main:\n  nop # First\n\n  hlt\n", vec![1, 3]);
+
+        assert_eq!(map.lines().len(), 4);
+        assert_eq!(map.line_of(0x1), Some(3));
+        assert_eq!(map.line_of(0x2), None);
+        assert_eq!(map.address_of_line(0), Some(0x0));
+        assert_eq!(map.address_of_line(2), Some(0x1));
+        assert_eq!(map.address_of_line(4), None);
+        assert_eq!(map.address_on_line(0), None);
+        assert_eq!(map.address_on_line(3), Some(0x1));
+    }
+}
@@ -0,0 +1,119 @@
+use std::collections::{BTreeMap, HashMap};
+
+use super::expression::parse_number;
+
+// Labels of a program, either defined in assembly sources or loaded from a
+// symbol file with one `name address` pair per line
+#[derive(Default, PartialEq, Eq)]
+pub struct SymbolTable {
+    addresses: HashMap<String, u16>,
+    labels: BTreeMap<u16, Vec<String>>, // Several labels may point to the same address
+}
+
+pub fn is_valid_name(name: &str) -> bool {
+    let mut chars = name.chars();
+    match chars.next() {
+        Some(ch) if ch.is_ascii_alphabetic() || ch == '_' || ch == '.' => {
+            chars.all(|ch| ch.is_ascii_alphanumeric() || ch == '_' || ch == '.')
+        }
+        _ => false,
+    }
+}
+
+impl SymbolTable {
+    pub fn parse(text: &str) -> Result<SymbolTable, String> {
+        let mut symbols = SymbolTable::default();
+
+        for (index, line) in text.lines().enumerate() {
+            let line = line.split('#').next().unwrap_or("").trim();
+            if line.is_empty() {
+                continue;
+            }
+
+            let result = match line.split_whitespace().collect::<Vec<&str>>().as_slice() {
+                [name, address] => parse_number(address).and_then(|a| symbols.insert(name, a)),
+                _ => Err(String::from("Expected 'name address'")),
+            };
+            result.map_err(|msg| format!("line {}: {}", index + 1, msg))?;
+        }
+
+        Ok(symbols)
+    }
+
+    pub fn insert(&mut self, name: &str, address: u16) -> Result<(), String> {
+        if !is_valid_name(name) {
+            return Err(format!("Invalid label '{}'", name));
+        }
+        if self.addresses.contains_key(name) {
+            return Err(format!("Duplicate label '{}'", name));
+        }
+
+        self.addresses.insert(String::from(name), address);
+        self.labels
+            .entry(address)
+            .or_default()
+            .push(String::from(name));
+        Ok(())
+    }
+
+    pub fn address_of(&self, name: &str) -> Option<u16> {
+        self.addresses.get(name).copied()
+    }
+
+    pub fn labels_at(&self, address: u16) -> &[String] {
+        self.labels
+            .get(&address)
+            .map_or(&[], |labels| labels.as_slice())
+    }
+
+    // The first label defined for the address
+    pub fn label_at(&self, address: u16) -> Option<&str> {
+        self.labels_at(address).first().map(String::as_str)
+    }
+
+    // The closest label at or before the address and its address
+    pub fn nearest_label(&self, address: u16) -> Option<(&str, u16)> {
+        self.labels
+            .range(..=address)
+            .next_back()
+            .and_then(|(label_address, labels)| Some((labels.first()?.as_str(), *label_address)))
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.addresses.is_empty()
+    }
+
+    pub fn len(&self) -> usize {
+        self.addresses.len()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parse_test() {
+        let symbols = SymbolTable::parse(
+            "# Symbols of fib.s\n\
+             main 0x0\n\
+             loop 3 # Loop body\n\
+             \n\
+             start 0x0\n",
+        )
+        .unwrap();
+
+        assert_eq!(symbols.len(), 3);
+        assert_eq!(symbols.address_of("loop"), Some(3));
+        assert_eq!(symbols.labels_at(0x0), ["main", "start"]);
+        assert_eq!(symbols.label_at(0x3), Some("loop"));
+        assert_eq!(symbols.label_at(0x4), None);
+        assert_eq!(symbols.nearest_label(0x5), Some(("loop", 3)));
+        assert_eq!(symbols.nearest_label(0x0), Some(("main", 0)));
+
+        assert!(SymbolTable::parse("main").is_err());
+        assert!(SymbolTable::parse("main 0x0\nmain 0x1").is_err());
+        assert!(SymbolTable::parse("1abc 0x0").is_err());
+        assert!(SymbolTable::parse("main zero").is_err());
+    }
+}
@@ -0,0 +1,202 @@
+// Expressions shown in the watch panel, e.g. `reg3`, `[buffer + 2]:x`, `reg2:s`
+// or `0x100..0x10f`. They are evaluated again after every step, values which
+// changed with the last step are reported so they can be highlighted.
+
+use super::expression::{format_value, Expr};
+use super::runtime::{CpuState, RAM_SIZE};
+use super::symbols::SymbolTable;
+
+// Longest string shown for a RAM range or a 0 terminated string
+const MAX_STRING_LENGTH: usize = 64;
+
+enum WatchValue {
+    Expr(Expr),
+    Range(Expr, Expr), // Inclusive RAM range, shown as a string
+}
+
+pub struct WatchExpression {
+    pub source: String,
+    value: WatchValue,
+    format: char, // x, b, c, d as in tracepoints, or s for strings
+}
+
+fn read_ram(cpu: &CpuState, address: u16) -> u16 {
+    match (address as usize) < RAM_SIZE {
+        true => cpu.ram[address as usize],
+        false => 0,
+    }
+}
+
+// One character per word, quoted and escaped
+fn format_string(words: impl Iterator<Item = u16>) -> String {
+    let mut text = String::new();
+    for (i, word) in words.enumerate() {
+        if i == MAX_STRING_LENGTH {
+            return format!("{:?}...", text);
+        }
+        text.push(char::from_u32(word as u32).unwrap_or('?'));
+    }
+    format!("{:?}", text)
+}
+
+impl WatchExpression {
+    // `<expr>[:x|b|c|d|s]` or `<start>..<end>[:s]`, labels are resolved right away
+    pub fn parse(spec: &str, symbols: &SymbolTable) -> Result<WatchExpression, String> {
+        let spec = spec.trim();
+        let (text, format) = match spec.rsplit_once(':') {
+            Some((text, format)) => match format.trim() {
+                format @ ("x" | "b" | "c" | "d" | "s") => (text, format.chars().next().unwrap()),
+                format => return Err(format!("Unknown format '{}'", format)),
+            },
+            None => (spec, 'd'),
+        };
+
+        let value = match text.split_once("..") {
+            Some((start, end)) => match format {
+                'd' | 's' => WatchValue::Range(
+                    Expr::parse_with(start, symbols)?,
+                    Expr::parse_with(end, symbols)?,
+                ),
+                _ => return Err(String::from("RAM ranges can only be shown as strings")),
+            },
+            None => WatchValue::Expr(Expr::parse_with(text, symbols)?),
+        };
+
+        Ok(WatchExpression {
+            source: String::from(spec),
+            value,
+            format,
+        })
+    }
+
+    pub fn evaluate(&self, cpu: &CpuState) -> String {
+        match &self.value {
+            WatchValue::Range(start, end) => {
+                let (start, end) = (start.evaluate(cpu), end.evaluate(cpu));
+                match start <= end {
+                    true => format_string((start..=end).map(|address| read_ram(cpu, address))),
+                    false => String::from("<empty range>"),
+                }
+            }
+            // The value is the address of a 0 terminated string
+            WatchValue::Expr(expr) if self.format == 's' => {
+                let start = expr.evaluate(cpu);
+                format_string(
+                    (0..=MAX_STRING_LENGTH as u16)
+                        .map(|offset| read_ram(cpu, start.wrapping_add(offset)))
+                        .take_while(|word| *word != 0),
+                )
+            }
+            WatchValue::Expr(expr) => format_value(expr.evaluate(cpu), self.format),
+        }
+    }
+}
+
+#[derive(Default)]
+pub struct WatchList {
+    expressions: Vec<WatchExpression>,
+    values: Vec<String>,   // As of the last refresh
+    previous: Vec<String>, // As of the refresh before the last step
+}
+
+impl WatchList {
+    pub fn add(&mut self, spec: &str, symbols: &SymbolTable) -> Result<usize, String> {
+        self.expressions
+            .push(WatchExpression::parse(spec, symbols)?);
+        Ok(self.expressions.len() - 1)
+    }
+
+    pub fn remove(&mut self, index: usize) -> Option<WatchExpression> {
+        if index >= self.expressions.len() {
+            return None;
+        }
+        // Drop the cached values, otherwise the following entries would compare
+        // against the values of their predecessors
+        self.values.clear();
+        self.previous.clear();
+        Some(self.expressions.remove(index))
+    }
+
+    pub fn clear(&mut self) {
+        *self = WatchList::default();
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = &WatchExpression> {
+        self.expressions.iter()
+    }
+
+    pub fn len(&self) -> usize {
+        self.expressions.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.expressions.is_empty()
+    }
+
+    // Evaluate all expressions against the current state
+    pub fn refresh(&mut self, cpu: &CpuState) {
+        self.values = self.expressions.iter().map(|e| e.evaluate(cpu)).collect();
+    }
+
+    // Called when the CPU executed instructions since the last refresh, the
+    // values of that refresh become the ones to compare against
+    pub fn step(&mut self) {
+        self.previous = self.values.clone();
+    }
+
+    // Expression, its value and whether it changed with the last step
+    pub fn values(&self) -> impl Iterator<Item = (&WatchExpression, &str, bool)> {
+        self.expressions
+            .iter()
+            .zip(self.values.iter())
+            .enumerate()
+            .map(|(i, (expr, value))| {
+                let changed = self.previous.get(i).is_some_and(|prev| prev != value);
+                (expr, value.as_str(), changed)
+            })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn watch_list_test() {
+        let mut cpu = CpuState::default();
+        let symbols = SymbolTable::parse("text 0x10").unwrap();
+        cpu.write_ram_string(0x10, "Hi\n").unwrap();
+        cpu.registers[2] = 0x10;
+
+        let mut watches = WatchList::default();
+        for spec in ["reg2:x", "[reg2] : c", "text..text+2", "reg2:s", "reg1 + 1"] {
+            watches.add(spec, &symbols).unwrap();
+        }
+        assert!(watches.add("reg2:q", &symbols).is_err());
+        assert!(watches.add("0..4:x", &symbols).is_err());
+        assert!(watches.add("nolabel", &symbols).is_err());
+
+        watches.refresh(&cpu);
+        let values: Vec<&str> = watches.values().map(|(_, value, _)| value).collect();
+        assert_eq!(values, ["0x10", "H", "\"Hi\\n\"", "\"Hi\\n\"", "1"]);
+        assert!(watches.values().all(|(_, _, changed)| !changed));
+
+        // Only values which differ from the ones before the step are changed
+        watches.step();
+        cpu.registers[1] = 5;
+        watches.refresh(&cpu);
+        let changed: Vec<bool> = watches.values().map(|(_, _, changed)| changed).collect();
+        assert_eq!(changed, [false, false, false, false, true]);
+
+        watches.step();
+        watches.refresh(&cpu);
+        assert!(watches.values().all(|(_, _, changed)| !changed));
+
+        assert_eq!(
+            watches.remove(0).map(|e| e.source),
+            Some(String::from("reg2:x"))
+        );
+        assert!(watches.remove(4).is_none());
+        assert_eq!(watches.len(), 4);
+    }
+}
@@ -0,0 +1,210 @@
+use super::expression::{evaluate_constant, parse_range_with};
+use super::runtime::{AccessKind, MemoryAccess};
+use super::symbols::SymbolTable;
+
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+pub enum WatchKind {
+    Read,
+    Write,
+    ReadWrite,
+    WriteValue(u16), // Only writes of this exact value
+}
+
+#[derive(Debug, PartialEq, Eq)]
+pub struct Watchpoint {
+    pub start: u16,
+    pub end: u16, // Inclusive
+    pub kind: WatchKind,
+    pub hit_count: u32,
+}
+
+impl Watchpoint {
+    // Parse a watchpoint from a specification of the form
+    // `<address>[..<end address>] [r|w|rw|w=<value>]`, e.g. `0x10`, `0x10..0x1f rw`
+    // or `0x20 w=5`. Without a kind, writes are watched.
+    pub fn parse(spec: &str) -> Result<Watchpoint, String> {
+        Watchpoint::parse_with(spec, &SymbolTable::default())
+    }
+
+    // Addresses and the value may be constant expressions using labels, without spaces
+    pub fn parse_with(spec: &str, symbols: &SymbolTable) -> Result<Watchpoint, String> {
+        let mut words = spec.split_whitespace();
+
+        let range = words.next().ok_or(String::from("Missing address"))?;
+        let (start, end) = parse_range_with(range, symbols)?;
+
+        let kind = match words.next() {
+            None | Some("w") => WatchKind::Write,
+            Some("r") => WatchKind::Read,
+            Some("rw") => WatchKind::ReadWrite,
+            Some(word) => match word.strip_prefix("w=") {
+                Some(value) => WatchKind::WriteValue(evaluate_constant(value, symbols)?),
+                None => return Err(format!("Unknown watch kind '{}'", word)),
+            },
+        };
+
+        if let Some(word) = words.next() {
+            return Err(format!("Unexpected '{}'", word));
+        }
+
+        Ok(Watchpoint {
+            start,
+            end,
+            kind,
+            hit_count: 0,
+        })
+    }
+
+    pub fn contains(&self, address: u16) -> bool {
+        (self.start..=self.end).contains(&address)
+    }
+
+    pub fn matches(&self, access: &MemoryAccess) -> bool {
+        if !self.contains(access.address) {
+            return false;
+        }
+
+        match (self.kind, access.kind) {
+            (WatchKind::ReadWrite, _) => true,
+            (WatchKind::Read, AccessKind::Read) => true,
+            (WatchKind::Write, AccessKind::Write) => true,
+            (WatchKind::WriteValue(value), AccessKind::Write) => value == access.value,
+            _ => false,
+        }
+    }
+
+    pub fn describe(&self) -> String {
+        let range = match self.start == self.end {
+            true => format!("{:#06x}", self.start),
+            false => format!("{:#06x}..{:#06x}", self.start, self.end),
+        };
+        let kind = match self.kind {
+            WatchKind::Read => String::from("read"),
+            WatchKind::Write => String::from("write"),
+            WatchKind::ReadWrite => String::from("read/write"),
+            WatchKind::WriteValue(value) => format!("write of {:#06x}", value),
+        };
+
+        format!("{} ({}), hits {}", range, kind, self.hit_count)
+    }
+}
+
+#[derive(Default)]
+pub struct WatchpointManager {
+    watchpoints: Vec<Watchpoint>,
+}
+
+impl WatchpointManager {
+    pub fn add(&mut self, watchpoint: Watchpoint) {
+        self.watchpoints.push(watchpoint);
+    }
+
+    pub fn get(&self, index: usize) -> Option<&Watchpoint> {
+        self.watchpoints.get(index)
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = &Watchpoint> {
+        self.watchpoints.iter()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.watchpoints.is_empty()
+    }
+
+    // Remove all watchpoints covering the address, returns the number removed
+    pub fn remove_at(&mut self, address: u16) -> usize {
+        let len_before = self.watchpoints.len();
+        self.watchpoints.retain(|wp| !wp.contains(address));
+        len_before - self.watchpoints.len()
+    }
+
+    pub fn clear(&mut self) {
+        self.watchpoints.clear();
+    }
+
+    pub fn is_watched(&self, address: u16) -> bool {
+        self.watchpoints.iter().any(|wp| wp.contains(address))
+    }
+
+    pub fn reset_hit_counts(&mut self) {
+        for watchpoint in self.watchpoints.iter_mut() {
+            watchpoint.hit_count = 0;
+        }
+    }
+
+    // Returns the index of the first watchpoint triggered by the access, if any
+    pub fn check(&mut self, access: &MemoryAccess) -> Option<usize> {
+        let mut triggered = None;
+
+        for (index, watchpoint) in self.watchpoints.iter_mut().enumerate() {
+            if watchpoint.matches(access) {
+                watchpoint.hit_count += 1;
+                triggered = triggered.or(Some(index));
+            }
+        }
+
+        triggered
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parse_test() {
+        assert_eq!(
+            Watchpoint::parse("0x10"),
+            Ok(Watchpoint {
+                start: 0x10,
+                end: 0x10,
+                kind: WatchKind::Write,
+                hit_count: 0
+            })
+        );
+        assert_eq!(
+            Watchpoint::parse("0x10..0x1f rw"),
+            Ok(Watchpoint {
+                start: 0x10,
+                end: 0x1f,
+                kind: WatchKind::ReadWrite,
+                hit_count: 0
+            })
+        );
+        assert_eq!(
+            Watchpoint::parse("32 w=0x5").map(|wp| wp.kind),
+            Ok(WatchKind::WriteValue(5))
+        );
+
+        assert!(Watchpoint::parse("").is_err());
+        assert!(Watchpoint::parse("0x20..0x10").is_err());
+        assert!(Watchpoint::parse("0x10 x").is_err());
+        assert!(Watchpoint::parse("0x10 r w").is_err());
+    }
+
+    #[test]
+    fn check_test() {
+        let mut manager = WatchpointManager::default();
+        manager.add(Watchpoint::parse("0x10 r").unwrap());
+        manager.add(Watchpoint::parse("0x20..0x2f").unwrap());
+        manager.add(Watchpoint::parse("0x30 w=7").unwrap());
+
+        let access = |kind, address, value| MemoryAccess {
+            kind,
+            address,
+            value,
+        };
+
+        assert_eq!(manager.check(&access(AccessKind::Read, 0x10, 0)), Some(0));
+        assert_eq!(manager.check(&access(AccessKind::Write, 0x10, 0)), None);
+        assert_eq!(manager.check(&access(AccessKind::Write, 0x25, 0)), Some(1));
+        assert_eq!(manager.check(&access(AccessKind::Read, 0x25, 0)), None);
+        assert_eq!(manager.check(&access(AccessKind::Write, 0x30, 6)), None);
+        assert_eq!(manager.check(&access(AccessKind::Write, 0x30, 7)), Some(2));
+        assert_eq!(manager.get(1).unwrap().hit_count, 1);
+
+        assert!(manager.is_watched(0x2f));
+        assert_eq!(manager.remove_at(0x2f), 1);
+        assert!(!manager.is_watched(0x2f));
+    }
+}
@@ -1,10 +1,11 @@
-use ratatui::prelude::{Constraint, CrosstermBackend, Direction, Layout};
+use ratatui::prelude::{Constraint, CrosstermBackend, Direction, Layout, Rect};
 
+use std::collections::HashMap;
 use std::error::Error;
+use std::fs;
 use std::io::{self, Stdout};
-use std::path::PathBuf;
-use std::str::FromStr;
-use std::time::Duration;
+use std::path::Path;
+use std::time::{Duration, SystemTime};
 
 use ratatui::Terminal;
 
@@ -15,17 +16,35 @@ use crossterm::{
 };
 
 use crate::backend;
+use crate::backend::annotations::check_bookmark_name;
+use crate::backend::assembler;
+use crate::backend::breakpoints::Condition;
+use crate::backend::changes::ChangeTracker;
+use crate::backend::debugger::{Debugger, RunTarget};
+use crate::backend::expression::{self, parse_range_with};
+use crate::backend::io_breaks::IoBreak;
 use crate::backend::program::Program;
 use crate::backend::runtime::CpuState;
+use crate::backend::search::Search;
+use crate::backend::watch_list::{WatchExpression, WatchList};
+use crate::backend::watchpoints::Watchpoint;
+use crate::frontend::commands::{self, Command, View};
+use crate::frontend::script::{parse_command, Script, MAX_STEPS};
+use crate::frontend::session;
 use crate::frontend::widgets::*;
 use crate::util::Timer;
 
 use super::log::*;
 
+// Maximum number of instructions executed per frame when running towards a target
+const RUN_BATCH_SIZE: u32 = 10_000;
+
 enum UiMode {
+    InspectRegisters,
     InspectTerminal,
     InspectRam,
     InspectProgram,
+    Console,
 }
 
 impl UiMode {
@@ -33,14 +52,18 @@ impl UiMode {
         match *self {
             UiMode::InspectRam => *self = UiMode::InspectTerminal,
             UiMode::InspectTerminal => *self = UiMode::InspectProgram,
-            UiMode::InspectProgram => *self = UiMode::InspectRam,
+            UiMode::InspectProgram => *self = UiMode::Console,
+            UiMode::Console => *self = UiMode::InspectRegisters,
+            UiMode::InspectRegisters => *self = UiMode::InspectRam,
         }
     }
 
     pub fn previous(&mut self) {
         match *self {
             UiMode::InspectTerminal => *self = UiMode::InspectRam,
-            UiMode::InspectRam => *self = UiMode::InspectProgram,
+            UiMode::InspectRam => *self = UiMode::InspectRegisters,
+            UiMode::InspectRegisters => *self = UiMode::Console,
+            UiMode::Console => *self = UiMode::InspectProgram,
             UiMode::InspectProgram => *self = UiMode::InspectTerminal,
         }
     }
@@ -49,7 +72,19 @@ impl UiMode {
 pub struct App {
     cpu: CpuState,
     program: Program,
+    program_path: Option<String>, // Where the program was loaded from, for saving
+    program_modified: Option<SystemTime>, // Modification time of the file when loaded
+    symbols_path: Option<String>, // Symbol file loaded with the symbols command
+    keep_ram_on_reload: bool,
+    debugger: Debugger,
+    watch_list: WatchList,
+    changes: ChangeTracker, // Recent changes made by the program, for highlighting
+
+    // Last searches, repeated with n/N in the RAM and program views
+    ram_search: Option<Search>,
+    program_search: Option<Search>,
     execution_timer: Timer,
+    reload_timer: Timer, // Interval for checking the program file for changes
 
     terminal: Terminal<CrosstermBackend<Stdout>>,
 
@@ -59,19 +94,22 @@ pub struct App {
     // Layout
     toplevel_layout: Layout,
     tty_layout: Layout,
-    cpustate_layout: Layout,
     rightpanel_layout: Layout,
     // Component states
     ram_widget_state: RamTableState,
     pmem_widget_state: PmemTableState,
+    branches_state: BranchesState,
     registers_widget_state: RegistersDisplayState,
     keybuffer_widget_state: KeybufferWidgetState,
+    console_state: ConsoleState,
+    prompt_history: HashMap<String, Vec<String>>, // Previous inputs, per prompt text
 
     message_log: Log,
 
     should_quit: bool,
     cpu_running: bool,
     cpu_step_requested: bool,
+    run_target: Option<RunTarget>, // Run at full speed until reached
 }
 
 impl App {
@@ -91,13 +129,16 @@ impl App {
             .direction(Direction::Vertical)
             .constraints([Constraint::Percentage(80), Constraint::Percentage(20)].as_ref());
 
-        let cpustate_layout = Layout::default()
-            .direction(Direction::Vertical)
-            .constraints([Constraint::Max(8), Constraint::Percentage(60)].as_ref());
-
         let rightpanel_layout = Layout::default()
             .direction(Direction::Vertical)
-            .constraints([Constraint::Percentage(70), Constraint::Percentage(30)].as_ref());
+            .constraints(
+                [
+                    Constraint::Percentage(65),
+                    Constraint::Min(3),
+                    Constraint::Length(3),
+                ]
+                .as_ref(),
+            );
 
         let mut stdout = io::stdout();
         let _ = enable_raw_mode();
@@ -114,20 +155,33 @@ impl App {
         App {
             cpu: CpuState::default(),
             program: Program::default(),
+            program_path: None,
+            program_modified: None,
+            symbols_path: None,
+            keep_ram_on_reload: false,
+            debugger: Debugger::default(),
+            watch_list: WatchList::default(),
+            changes: ChangeTracker::default(),
+            ram_search: None,
+            program_search: None,
             execution_timer: Timer::new(Duration::from_millis(250)),
+            reload_timer: Timer::new(Duration::from_millis(500)),
             cpu_running: false,
             cpu_step_requested: false,
+            run_target: None,
 
             ui_mode: UiMode::InspectTerminal,
             should_quit: false,
 
             toplevel_layout,
             tty_layout,
-            cpustate_layout,
             ram_widget_state: RamTableState::default(),
             pmem_widget_state: PmemTableState::default(),
+            branches_state: BranchesState::default(),
             registers_widget_state: RegistersDisplayState::default(),
             keybuffer_widget_state: KeybufferWidgetState { focused: true },
+            console_state: ConsoleState::default(),
+            prompt_history: HashMap::new(),
 
             terminal,
             message_log: log,
@@ -136,50 +190,121 @@ impl App {
     }
 
     fn draw(&mut self) {
+        self.watch_list.refresh(&self.cpu);
+
+        // The watch panel below the registers grows with its entries
+        let watch_height = match self.watch_list.len() {
+            0 => 0,
+            n => n.min(8) as u16 + 2,
+        };
+        let cpustate_layout = Layout::default()
+            .direction(Direction::Vertical)
+            .constraints(
+                [
+                    Constraint::Max(8),
+                    Constraint::Length(watch_height),
+                    Constraint::Percentage(60),
+                ]
+                .as_ref(),
+            );
+
         self.terminal
             .draw(|frame| {
                 // Resolve layout
                 let toplevel_chunks = self.toplevel_layout.split(frame.size());
                 let tty_chunks = self.tty_layout.split(toplevel_chunks[1]);
-                let cpustate_chunks = self.cpustate_layout.split(toplevel_chunks[0]);
+                let cpustate_chunks = cpustate_layout.split(toplevel_chunks[0]);
                 let rightpanel_chunks = self.rightpanel_layout.split(toplevel_chunks[2]);
+                // The recent branches are shown next to the program
+                let program_chunks = Layout::default()
+                    .direction(Direction::Horizontal)
+                    .constraints(match self.branches_state.visible {
+                        true => [Constraint::Percentage(65), Constraint::Percentage(35)],
+                        false => [Constraint::Percentage(100), Constraint::Percentage(0)],
+                    })
+                    .split(rightpanel_chunks[0]);
 
-                let ram_table = RamTableWidget::new(&self.cpu);
-                let pmem_table = PmemTableWidget::new(&self.cpu, &self.program);
-                let register_display = RegistersDisplayWidget::new(&self.cpu);
+                let ram_table =
+                    RamTableWidget::new(&self.cpu, &self.debugger.watchpoints, &self.changes);
+                let pmem_table = PmemTableWidget::new(
+                    &self.cpu,
+                    &self.program,
+                    &self.debugger.breakpoints,
+                    &self.debugger.annotations,
+                );
+                let register_display = RegistersDisplayWidget::new(&self.cpu, &self.changes);
                 let keybuffer_widget = KeybufferWidget::new(&self.cpu.istream.string);
                 let terminal_widget = TerminalWidget::new(&self.cpu.ostream.string);
                 let log_widget = LogWidget::new(&self.message_log);
+                let console_widget = ConsoleWidget::default();
 
                 // Set the right widget to focussed
                 match self.ui_mode {
                     UiMode::InspectTerminal => {
+                        self.registers_widget_state.is_focussed = false;
                         self.keybuffer_widget_state.focused = true;
                         self.ram_widget_state.is_focussed = false;
                         self.pmem_widget_state.is_focussed = false;
+                        self.console_state.is_focussed = false;
                     }
                     UiMode::InspectRam => {
+                        self.registers_widget_state.is_focussed = false;
                         self.keybuffer_widget_state.focused = false;
                         self.ram_widget_state.is_focussed = true;
                         self.pmem_widget_state.is_focussed = false;
+                        self.console_state.is_focussed = false;
                     }
                     UiMode::InspectProgram => {
+                        self.registers_widget_state.is_focussed = false;
                         self.keybuffer_widget_state.focused = false;
                         self.ram_widget_state.is_focussed = false;
                         self.pmem_widget_state.is_focussed = true;
+                        self.console_state.is_focussed = false;
+                    }
+                    UiMode::InspectRegisters => {
+                        self.registers_widget_state.is_focussed = true;
+                        self.keybuffer_widget_state.focused = false;
+                        self.ram_widget_state.is_focussed = false;
+                        self.pmem_widget_state.is_focussed = false;
+                        self.console_state.is_focussed = false;
+                    }
+                    UiMode::Console => {
+                        self.registers_widget_state.is_focussed = false;
+                        self.keybuffer_widget_state.focused = false;
+                        self.ram_widget_state.is_focussed = false;
+                        self.pmem_widget_state.is_focussed = false;
+                        self.console_state.is_focussed = true;
                     }
                 }
 
                 frame.render_stateful_widget(
                     ram_table,
-                    cpustate_chunks[1],
+                    cpustate_chunks[2],
                     &mut self.ram_widget_state,
                 );
-                frame.render_stateful_widget(
-                    pmem_table,
-                    rightpanel_chunks[0],
-                    &mut self.pmem_widget_state,
-                );
+                if !self.watch_list.is_empty() {
+                    frame.render_widget(WatchesWidget::new(&self.watch_list), cpustate_chunks[1]);
+                }
+                match &self.program.source {
+                    Some(source) if self.pmem_widget_state.show_source => frame
+                        .render_stateful_widget(
+                            SourceWidget::new(&self.cpu, source, &self.debugger.breakpoints),
+                            program_chunks[0],
+                            &mut self.pmem_widget_state,
+                        ),
+                    _ => frame.render_stateful_widget(
+                        pmem_table,
+                        program_chunks[0],
+                        &mut self.pmem_widget_state,
+                    ),
+                }
+                if self.branches_state.visible {
+                    frame.render_stateful_widget(
+                        BranchesWidget::new(&self.cpu.branches, &self.program),
+                        program_chunks[1],
+                        &mut self.branches_state,
+                    );
+                }
                 frame.render_stateful_widget(
                     register_display,
                     cpustate_chunks[0],
@@ -192,6 +317,11 @@ impl App {
                 );
                 frame.render_widget(terminal_widget, tty_chunks[0]);
                 frame.render_widget(log_widget, rightpanel_chunks[1]);
+                frame.render_stateful_widget(
+                    console_widget,
+                    rightpanel_chunks[2],
+                    &mut self.console_state,
+                );
             })
             .unwrap();
     }
@@ -216,16 +346,44 @@ impl App {
         }
     }
 
-    fn prompt<T: FromStr>(&mut self, prompt_text: &str) -> Option<T> {
-        let mut input_buffer = String::new();
+    fn prompt<T: PromptValue>(&mut self, prompt_text: &str) -> Option<T> {
+        self.prompt_with(prompt_text, |text, _| T::parse_prompt(text))
+    }
+
+    // Numeric input, an expression evaluated against the current CPU state and
+    // the labels of the program, e.g. `[reg2 + 4]` or `buffer + 0x10`
+    fn prompt_value(&mut self, prompt_text: &str) -> Option<u16> {
+        self.prompt_with(prompt_text, |text, app| {
+            expression::evaluate(text, &app.cpu, &app.program.symbols)
+        })
+    }
 
+    fn prompt_with<T>(
+        &mut self,
+        prompt_text: &str,
+        parse: impl Fn(&str, &Self) -> Result<T, String>,
+    ) -> Option<T> {
+        let history = self.prompt_history.remove(prompt_text).unwrap_or_default();
+        let mut state = PromptState::new(history);
+        let value = self.run_prompt(prompt_text, &mut state, parse);
+        self.prompt_history
+            .insert(String::from(prompt_text), state.into_history());
+        value
+    }
+
+    fn run_prompt<T>(
+        &mut self,
+        prompt_text: &str,
+        state: &mut PromptState,
+        parse: impl Fn(&str, &Self) -> Result<T, String>,
+    ) -> Option<T> {
         loop {
-            let prompt_widget = PromptWidget::new(prompt_text, &input_buffer);
+            let prompt_widget = PromptWidget::new(prompt_text);
             let vertical_area = Layout::default()
                 .direction(Direction::Vertical)
                 .constraints([
                     Constraint::Percentage(33),
-                    Constraint::Max(4),
+                    Constraint::Max(5),
                     Constraint::Percentage(33),
                 ])
                 .split(self.terminal.get_frame().size())[1];
@@ -238,25 +396,79 @@ impl App {
                 ])
                 .split(vertical_area)[1];
             self.terminal
-                .draw(|frame| frame.render_widget(prompt_widget, area))
+                .draw(|frame| frame.render_stateful_widget(prompt_widget, area, state))
                 .unwrap();
 
             if event::poll(Duration::from_millis(500)).unwrap() {
                 if let crossterm::event::Event::Key(key) = event::read().unwrap() {
                     match key.code {
-                        KeyCode::Char(c) => input_buffer.push(c),
-                        KeyCode::Backspace => {
-                            let _ = input_buffer.pop();
-                        }
+                        KeyCode::Char(c) => state.insert(c),
+                        KeyCode::Backspace => state.backspace(),
+                        KeyCode::Delete => state.delete(),
+                        KeyCode::Left => state.move_cursor(-1),
+                        KeyCode::Right => state.move_cursor(1),
+                        KeyCode::Home => state.home(),
+                        KeyCode::End => state.end(),
+                        KeyCode::Up => state.history_previous(),
+                        KeyCode::Down => state.history_next(),
                         KeyCode::Esc => {
                             return None;
                         }
-                        KeyCode::Enter => match str::parse::<T>(&input_buffer) {
-                            Ok(val) => return Some(val),
-                            Err(_) => {
-                                input_buffer.clear();
+                        KeyCode::Enter => {
+                            if let Some(value) = state.submit_with(|text| parse(text, self)) {
+                                return Some(value);
                             }
-                        },
+                        }
+                        _ => {}
+                    }
+                }
+            }
+        }
+    }
+
+    // Show the file browser until a file is picked or it is cancelled
+    fn browse_file(&mut self) -> Option<String> {
+        // Start in the directory of the current program
+        let start = self
+            .program_path
+            .as_ref()
+            .and_then(|path| Path::new(path).parent())
+            .map(|dir| dir.to_string_lossy().into_owned())
+            .filter(|dir| !dir.is_empty())
+            .map(|dir| dir + "/")
+            .unwrap_or_default();
+        let mut state = FileBrowserState::new(start, session::recent_files());
+
+        loop {
+            let size = self.terminal.get_frame().size();
+            let area = Rect {
+                x: size.width / 6,
+                y: size.height / 6,
+                width: size.width - size.width / 3,
+                height: size.height - size.height / 3,
+            };
+            self.terminal
+                .draw(|frame| {
+                    frame.render_stateful_widget(FileBrowserWidget::default(), area, &mut state)
+                })
+                .unwrap();
+
+            if event::poll(Duration::from_millis(500)).unwrap() {
+                if let crossterm::event::Event::Key(key) = event::read().unwrap() {
+                    match key.code {
+                        KeyCode::Char(c) => state.push(c),
+                        KeyCode::Backspace => state.pop(),
+                        KeyCode::Tab => state.complete(),
+                        KeyCode::Up => state.move_selection(-1),
+                        KeyCode::Down => state.move_selection(1),
+                        KeyCode::PageUp => state.move_selection(-10),
+                        KeyCode::PageDown => state.move_selection(10),
+                        KeyCode::Enter => {
+                            if let Some(path) = state.accept() {
+                                return Some(path);
+                            }
+                        }
+                        KeyCode::Esc => return None,
                         _ => {}
                     }
                 }
@@ -265,31 +477,654 @@ impl App {
     }
 
     pub fn try_load_program(&mut self, path: String) -> bool {
-        let res = backend::hex_parser::bytevec_from_hexfile(path.clone());
-        match res {
-            Err(_) => {
+        match Program::load(&path) {
+            Err(msg) => {
                 self.message_log.log(Message::new(
                     MessageType::Error,
-                    format!("Failed to load '{}'", path),
+                    format!("Failed to load '{}': {}", path, msg),
                 ));
                 false
             }
-            Ok(bytes) => {
+            Ok(program) => {
+                // The debugger state belongs to the previous program
+                self.save_session();
+                self.debugger = Debugger::default();
+                self.watch_list.clear();
+                self.symbols_path = None;
+
                 self.reset_cpu();
-                self.program = Program::from(bytes.as_slice());
+                self.program = program;
                 self.message_log.log(Message::new(
                     MessageType::Info,
-                    format!("Loaded '{}'", path),
+                    match self.program.symbols.len() {
+                        0 => format!("Loaded '{}'", path),
+                        n => format!("Loaded '{}' with {} symbols", path, n),
+                    },
                 ));
+                self.program_modified = modification_time(&path);
+                if let Err(err) = session::add_recent_file(&path) {
+                    self.message_log.log(Message::new(
+                        MessageType::Warning,
+                        format!("Failed to update recent files: {}", err),
+                    ));
+                }
+                self.program_path = Some(path);
+                self.restore_session();
                 true
             }
         }
     }
 
+    // Load the most recently loaded program, if it still exists
+    pub fn load_last_program(&mut self) {
+        let last = session::recent_files().into_iter().next();
+        if let Some(path) = last.filter(|path| Path::new(path).exists()) {
+            self.try_load_program(path);
+        }
+    }
+
+    // Store breakpoints, watchpoints and view state of the current program
+    fn save_session(&mut self) {
+        let Some(path) = self.program_path.clone() else {
+            return;
+        };
+
+        let mut commands = Vec::new();
+        if let Some(symbols_path) = &self.symbols_path {
+            commands.push(format!("symbols {}", symbols_path));
+        }
+        commands.push(format!(
+            "delay {}",
+            self.execution_timer.duration().as_millis()
+        ));
+        commands.append(&mut session::debugger_commands(&self.debugger));
+        for expr in self.watch_list.iter() {
+            commands.push(format!("display {}", expr.source));
+        }
+
+        let program_view = match self.pmem_widget_state.show_source {
+            true => "source",
+            false => "program",
+        };
+        let program_command = match self.pmem_widget_state.focus_executing {
+            true => format!("view {}", program_view),
+            false => format!(
+                "view {} {:#06x}",
+                program_view, self.pmem_widget_state.selected
+            ),
+        };
+        commands.push(format!("view ram {:#06x}", self.ram_widget_state.cursor));
+        commands.push(program_command.clone());
+        // The last view command decides the active context
+        commands.push(match self.ui_mode {
+            UiMode::InspectRam => format!("view ram {:#06x}", self.ram_widget_state.cursor),
+            UiMode::InspectProgram => program_command,
+            UiMode::InspectRegisters => String::from("view registers"),
+            UiMode::InspectTerminal | UiMode::Console => String::from("view terminal"),
+        });
+
+        if let Err(err) = session::write_session(&path, &commands) {
+            self.message_log.log(Message::new(
+                MessageType::Error,
+                format!("Failed to save session of '{}': {}", path, err),
+            ));
+        }
+    }
+
+    // Execute the stored session of the current program, if there is one
+    fn restore_session(&mut self) {
+        let Some(path) = self.program_path.clone() else {
+            return;
+        };
+        let Some(commands) = session::read_session(&path) else {
+            return;
+        };
+
+        for (line, text) in commands {
+            let result =
+                Command::parse_with(&text, &self.program.symbols).and_then(
+                    |command| match command {
+                        Command::Delay(ms) => {
+                            self.execution_timer.set_duration(Duration::from_millis(ms));
+                            Ok(())
+                        }
+                        Command::Symbols(path) => {
+                            self.program.load_symbols(&path)?;
+                            self.symbols_path = Some(path);
+                            Ok(())
+                        }
+                        Command::View(view) => {
+                            self.show_view(view);
+                            Ok(())
+                        }
+                        Command::Display(spec) => self.add_watch(&spec).map(|_| ()),
+                        command => match command.execute(&mut self.cpu, &mut self.debugger) {
+                            Some(Err(msg)) => Err(msg),
+                            _ => Ok(()),
+                        },
+                    },
+                );
+            if let Err(msg) = result {
+                self.message_log.log(Message::new(
+                    MessageType::Warning,
+                    format!("Session line {}: {}", line, msg),
+                ));
+            }
+        }
+
+        self.message_log.log(Message::new(
+            MessageType::Info,
+            format!("Restored session of '{}'", path),
+        ));
+    }
+
+    // Move the RAM cursor to the last written address, if enabled
+    fn follow_ram_write(&mut self) {
+        let state = &mut self.ram_widget_state;
+        if let Some(address) = self.changes.last_ram_write {
+            if state.follow_writes && !state.is_editing() {
+                state.cursor = address;
+            }
+        }
+    }
+
+    fn add_watch(&mut self, spec: &str) -> Result<String, String> {
+        let index = self.watch_list.add(spec, &self.program.symbols)?;
+        Ok(format!("Watch expression {}: {}", index + 1, spec.trim()))
+    }
+
+    // Remove the n-th (counting from 1) watch expression, or all of them
+    fn remove_watch(&mut self, n: Option<usize>) -> Result<String, String> {
+        match n {
+            Some(n) => match self.watch_list.remove(n - 1) {
+                Some(expr) => Ok(format!("Removed watch expression {}: {}", n, expr.source)),
+                None => Err(format!("No watch expression {}", n)),
+            },
+            None => {
+                self.watch_list.clear();
+                Ok(String::from("Removed all watch expressions"))
+            }
+        }
+    }
+
+    // Walk through the recent branches, selecting the jump instruction of each
+    fn goto_branch(&mut self, older: bool) {
+        self.branches_state.visible = true;
+        match self.branches_state.select(&self.cpu.branches, older) {
+            Some(branch) => {
+                self.pmem_widget_state.focus_executing = false;
+                self.pmem_widget_state.selected = branch.source as u32;
+            }
+            None => self.message_log.log(Message::new(
+                MessageType::Warning,
+                String::from("No jumps taken since the last reset"),
+            )),
+        }
+    }
+
+    // Select the next or previous instruction with a bookmark or comment
+    fn goto_annotation(&mut self, forward: bool) {
+        let from = match self.pmem_widget_state.focus_executing {
+            true => self.cpu.pcounter,
+            false => self.pmem_widget_state.selected as u16,
+        };
+        match self.debugger.annotations.next(from, forward) {
+            Some(address) => {
+                self.pmem_widget_state.focus_executing = false;
+                self.pmem_widget_state.selected = address as u32;
+            }
+            None => self.message_log.log(Message::new(
+                MessageType::Warning,
+                String::from("No bookmarks or comments set, add them with M and ;"),
+            )),
+        }
+    }
+
+    // Ask for a pattern and go to the first match after the cursor
+    fn prompt_search(&mut self, target: &str, text: &str) {
+        let opt = self.prompt_with(text, |text, app| {
+            Search::parse(&format!("{} {}", target, text), &app.program.symbols)
+        });
+        if let Some(search) = opt {
+            self.start_search(search);
+        }
+    }
+
+    fn start_search(&mut self, search: Search) {
+        let summary = search.summary(&self.cpu, &self.program);
+        self.message_log
+            .log(Message::new(MessageType::Info, summary));
+
+        let is_ram = matches!(search, Search::Ram(_));
+        match is_ram {
+            true => self.ram_search = Some(search),
+            false => self.program_search = Some(search),
+        }
+        self.repeat_search(is_ram, true);
+    }
+
+    // Go to the next or previous match of the last RAM or program search
+    fn repeat_search(&mut self, ram: bool, forward: bool) {
+        let search = match ram {
+            true => &self.ram_search,
+            false => &self.program_search,
+        };
+        let Some(search) = search else {
+            self.message_log.log(Message::new(
+                MessageType::Warning,
+                String::from("Nothing searched yet, press / to search"),
+            ));
+            return;
+        };
+
+        let from = match (ram, self.pmem_widget_state.focus_executing) {
+            (true, _) => self.ram_widget_state.cursor,
+            (false, true) => self.cpu.pcounter,
+            (false, false) => self.pmem_widget_state.selected as u16,
+        };
+        let Some(address) = search.find(&self.cpu, &self.program, from, forward) else {
+            self.message_log.log(Message::new(
+                MessageType::Warning,
+                String::from("No matches"),
+            ));
+            return;
+        };
+
+        // The program view stays in source mode if it is in it
+        match ram {
+            true => self.show_view(View::Ram(Some(address))),
+            false => {
+                self.ui_mode = UiMode::InspectProgram;
+                self.pmem_widget_state.focus_executing = false;
+                self.pmem_widget_state.selected = address as u32;
+            }
+        }
+    }
+
+    fn show_view(&mut self, view: View) {
+        match view {
+            View::Ram(address) => {
+                self.ui_mode = UiMode::InspectRam;
+                if let Some(address) = address {
+                    self.ram_widget_state.goto_address(address as u32);
+                }
+            }
+            View::Program(address) | View::Source(address) => {
+                self.ui_mode = UiMode::InspectProgram;
+                self.pmem_widget_state.show_source = matches!(view, View::Source(_));
+                self.pmem_widget_state.focus_executing = address.is_none();
+                if let Some(address) = address {
+                    self.pmem_widget_state.selected = address as u32;
+                }
+            }
+            View::Registers => self.ui_mode = UiMode::InspectRegisters,
+            View::Terminal => self.ui_mode = UiMode::InspectTerminal,
+        }
+    }
+
+    // Reload the program if its file was changed, e.g. by re-assembling it.
+    // Breakpoints move along with the labels, RAM is optionally kept.
+    // Expressions using labels are parsed again with the new symbols.
+    fn reload_if_modified(&mut self) {
+        if !self.reload_timer.has_elapsed_reset() {
+            return;
+        }
+        let Some(path) = self.program_path.clone() else {
+            return;
+        };
+        let Some(modified) = modified_since(&path, self.program_modified) else {
+            return;
+        };
+        // Only try once per change, the file may still be incomplete
+        self.program_modified = Some(modified);
+
+        let loaded = Program::load(&path).and_then(|mut program| {
+            if let Some(symbols_path) = &self.symbols_path {
+                program.load_symbols(symbols_path)?;
+            }
+            Ok(program)
+        });
+        let program = match loaded {
+            Ok(program) => program,
+            Err(msg) => {
+                self.message_log.log(Message::new(
+                    MessageType::Error,
+                    format!("Failed to reload '{}': {}", path, msg),
+                ));
+                return;
+            }
+        };
+
+        let old_program = std::mem::replace(&mut self.program, program);
+        let dropped = self
+            .debugger
+            .breakpoints
+            .relocate(|address| old_program.relocate(address, &self.program));
+        for breakpoint in dropped {
+            self.message_log.log(Message::new(
+                MessageType::Warning,
+                format!(
+                    "Removed breakpoint {}, it moved onto another breakpoint",
+                    breakpoint.describe()
+                ),
+            ));
+        }
+        self.debugger
+            .annotations
+            .relocate(|address| old_program.relocate(address, &self.program));
+
+        // Labels in conditions, messages and watch expressions may have moved
+        for warning in self.debugger.breakpoints.reparse(&self.program.symbols) {
+            self.message_log
+                .log(Message::new(MessageType::Warning, warning));
+        }
+        // Watchpoints are RAM addresses, which labels of the program may point to.
+        // Only their addresses are known, so they cannot follow the labels.
+        if !self.debugger.watchpoints.is_empty() && old_program.symbols != self.program.symbols {
+            self.message_log.log(Message::new(
+                MessageType::Warning,
+                String::from("Labels changed, watchpoints keep their addresses"),
+            ));
+        }
+        let specs: Vec<String> = self.watch_list.iter().map(|e| e.source.clone()).collect();
+        self.watch_list.clear();
+        for spec in specs {
+            if let Err(msg) = self.add_watch(&spec) {
+                self.message_log.log(Message::new(
+                    MessageType::Warning,
+                    format!("Removed watch expression '{}': {}", spec, msg),
+                ));
+            }
+        }
+
+        let ram = self.cpu.ram;
+        self.reset_cpu();
+        if self.keep_ram_on_reload {
+            self.cpu.ram = ram;
+        }
+
+        self.message_log.log(Message::new(
+            MessageType::Info,
+            format!(
+                "Reloaded '{}' ({} instructions, RAM {})",
+                path,
+                self.program.operations.len(),
+                match self.keep_ram_on_reload {
+                    true => "kept",
+                    false => "cleared",
+                }
+            ),
+        ));
+    }
+
+    // Execute a script file line by line like console commands, stopping at the
+    // first one that logs an error
+    pub fn run_script(&mut self, path: &str) {
+        let script = match Script::load(path) {
+            Ok(script) => script,
+            Err(msg) => {
+                self.message_log.log(Message::new(MessageType::Error, msg));
+                return;
+            }
+        };
+
+        self.cpu_running = false;
+        self.run_target = None;
+
+        for (number, line) in script.lines() {
+            if let Err(msg) = self.run_script_line(line) {
+                self.message_log.log(Message::new(
+                    MessageType::Error,
+                    format!("{}:{}: {}", path, number, msg),
+                ));
+                return;
+            }
+        }
+        self.message_log.log(Message::new(
+            MessageType::Info,
+            format!("Finished script '{}'", path),
+        ));
+    }
+
+    fn run_script_line(&mut self, line: &str) -> Result<(), String> {
+        parse_command(line, &self.program.symbols)?;
+
+        let logged = self.message_log.messages.len();
+        self.execute_command(line);
+        self.finish_execution()?;
+
+        let failed = self
+            .message_log
+            .messages
+            .get(logged..)
+            .is_some_and(|messages| {
+                messages
+                    .iter()
+                    .any(|message| matches!(message.message_type, MessageType::Error))
+            });
+        match failed {
+            true => Err(String::from("Stopped script")),
+            false => Ok(()),
+        }
+    }
+
+    // Scripts wait for commands which run the CPU, instead of letting them
+    // continue in the background
+    fn finish_execution(&mut self) -> Result<(), String> {
+        let mut steps = 0;
+        while self.cpu_step_requested || self.cpu_running || self.run_target.is_some() {
+            if self.cpu.received_halt {
+                self.stop_execution();
+                return Err(String::from("CPU is halted"));
+            }
+            if self.cpu.pcounter as usize >= self.program.operations.len() {
+                self.stop_execution();
+                return Err(format!(
+                    "Program counter {:#X} is outside of the program",
+                    self.cpu.pcounter
+                ));
+            }
+            if steps >= MAX_STEPS {
+                self.stop_execution();
+                return Err(format!("Gave up after {} instructions", MAX_STEPS));
+            }
+
+            let executed = self.cpu.executed;
+            match self.run_target.is_some() {
+                true => self.run_towards_target(),
+                false => {
+                    self.execute_instruction();
+                    self.cpu_step_requested = false;
+                }
+            }
+            steps += self.cpu.executed - executed;
+            if self.cpu.executed != executed {
+                self.watch_list.step();
+            }
+        }
+        Ok(())
+    }
+
+    fn stop_execution(&mut self) {
+        self.cpu_step_requested = false;
+        self.cpu_running = false;
+        self.run_target = None;
+    }
+
+    fn save_program(&mut self, path: String) {
+        if path.is_empty() {
+            return;
+        }
+
+        match write_program(&path, &self.program) {
+            Ok(modified) => {
+                self.message_log.log(Message::new(
+                    MessageType::Info,
+                    format!("Saved program to '{}'", path),
+                ));
+                // Our own write must not look like a change to reload. A copy saved
+                // elsewhere, e.g. a patched .hex of a .s file, is not the program
+                // file, so hot reload and the session stay with the loaded one.
+                if self
+                    .program_path
+                    .as_deref()
+                    .is_some_and(|loaded| same_file(loaded, &path))
+                {
+                    self.program_modified = modified;
+                }
+            }
+            Err(err) => self.message_log.log(Message::new(
+                MessageType::Error,
+                format!("Failed to save '{}': {}", path, err),
+            )),
+        }
+    }
+
     fn reset_cpu(&mut self) {
         self.cpu_step_requested = false;
         self.cpu_running = false;
+        self.run_target = None;
         self.cpu = CpuState::default();
+        self.changes.clear();
+        self.debugger.reset_hit_counts();
+    }
+
+    // Execute the next instruction and stop free running if the debugger says so.
+    // Returns true if execution was stopped.
+    fn execute_instruction(&mut self) -> bool {
+        let before = ChangeTracker::snapshot(&self.cpu);
+        let stop_reason = self.debugger.step(&mut self.cpu, &self.program);
+        self.changes.record(&before, &self.cpu);
+
+        for message in self.debugger.take_trace_messages() {
+            self.message_log
+                .log(Message::new(MessageType::Info, message));
+        }
+
+        match stop_reason {
+            Some(reason) => {
+                self.cpu_running = false;
+                self.run_target = None;
+                self.message_log
+                    .log(Message::new(MessageType::Info, reason.describe()));
+                true
+            }
+            None => false,
+        }
+    }
+
+    fn start_run_target(&mut self, target: RunTarget) {
+        self.message_log.log(Message::new(
+            MessageType::Info,
+            format!("Running until {}", target.describe()),
+        ));
+        self.cpu_running = false;
+        self.run_target = Some(target);
+    }
+
+    // Execute a batch of instructions at full speed, unless the target is reached
+    // or the debugger stops execution first
+    fn run_towards_target(&mut self) {
+        for _ in 0..RUN_BATCH_SIZE {
+            if self.execute_instruction() {
+                return;
+            }
+
+            let reached = match self.run_target.as_mut() {
+                Some(target) => target.is_reached(&self.cpu),
+                None => return,
+            };
+            if reached {
+                let target = self.run_target.take().unwrap();
+                self.message_log.log(Message::new(
+                    MessageType::Info,
+                    format!(
+                        "Stopped at {:#X} ({})",
+                        self.cpu.pcounter,
+                        target.describe()
+                    ),
+                ));
+                return;
+            }
+        }
+    }
+
+    fn step_over_loop(&mut self) {
+        match self.program.enclosing_loop(self.cpu.pcounter) {
+            Some((start, end)) => self.start_run_target(RunTarget::LeaveRange { start, end }),
+            None => self.message_log.log(Message::new(
+                MessageType::Warning,
+                format!("No loop around {:#X}", self.cpu.pcounter),
+            )),
+        }
+    }
+
+    fn execute_command(&mut self, line: &str) {
+        self.message_log
+            .log(Message::new(MessageType::Info, format!("> {}", line)));
+
+        let command = match Command::parse_with(line, &self.program.symbols) {
+            Ok(command) => command,
+            Err(msg) => {
+                self.message_log.log(Message::new(MessageType::Error, msg));
+                return;
+            }
+        };
+
+        match command {
+            Command::Step(1) => self.cpu_step_requested = true,
+            Command::Step(n) => self.start_run_target(RunTarget::Steps(n)),
+            Command::Continue => {
+                self.run_target = None;
+                self.cpu_running = true;
+            }
+            Command::Until(condition) => self.start_run_target(RunTarget::Condition(condition)),
+            Command::Advance(address) => self.start_run_target(RunTarget::Address(address)),
+            Command::Over => self.step_over_loop(),
+            Command::Load(path) => {
+                self.try_load_program(path);
+            }
+            Command::Reset => {
+                self.reset_cpu();
+                self.message_log
+                    .log(Message::new(MessageType::Info, String::from("Reset CPU")));
+            }
+            Command::Delay(ms) => self.execution_timer.set_duration(Duration::from_millis(ms)),
+            Command::Clear => self.message_log.clear(),
+            Command::Source(path) => self.run_script(&path),
+            Command::Symbols(path) => {
+                let result = self.program.load_symbols(&path).map(|()| {
+                    self.symbols_path = Some(path);
+                    format!("Loaded {} symbols", self.program.symbols.len())
+                });
+                self.log_result(result);
+            }
+            Command::View(view) => self.show_view(view),
+            Command::Display(spec) => {
+                let result = self.add_watch(&spec);
+                self.log_result(result);
+            }
+            Command::Undisplay(n) => {
+                let result = self.remove_watch(n);
+                self.log_result(result);
+            }
+            Command::Find(search) => self.start_search(search),
+            command => self.execute_debugger_command(command),
+        }
+    }
+
+    // Commands which only change the CPU or debugger state, their output goes
+    // to the message log
+    fn execute_debugger_command(&mut self, command: Command) {
+        match command.execute(&mut self.cpu, &mut self.debugger) {
+            Some(Ok(lines)) => {
+                for line in lines {
+                    self.message_log.log(Message::new(MessageType::Info, line));
+                }
+            }
+            Some(Err(msg)) => self.message_log.log(Message::new(MessageType::Error, msg)),
+            None => {}
+        }
     }
 
     fn update_cpu(&mut self) {
@@ -298,51 +1133,37 @@ impl App {
             return;
         }
 
+        // Running towards a target ignores the execution delay
+        if self.run_target.is_some() {
+            self.run_towards_target();
+            return;
+        }
+
         // Free running
         if self.cpu_running && self.execution_timer.has_elapsed() {
-            self.cpu.execute_next_prog_op(&self.program);
+            self.execute_instruction();
             self.execution_timer.reset();
-            if self.cpu.received_halt {
-                self.message_log.log(Message::new(
-                    MessageType::Info,
-                    String::from("CPU received halt."),
-                ))
-            }
         }
 
         // Single step
         if self.cpu_step_requested {
-            self.cpu.execute_next_prog_op(&self.program);
-            if self.cpu.received_halt {
-                self.message_log.log(Message::new(
-                    MessageType::Info,
-                    String::from("CPU received halt."),
-                ))
-            }
+            self.execute_instruction();
             self.cpu_step_requested = false;
         }
-
-        // Stop if a breakpoint got reached
-        if (self.cpu_running || self.cpu_step_requested)
-            && self.program.breakpoints[self
-                .cpu
-                .pcounter
-                .clamp(0, (self.program.operations.len() - 1) as u16)
-                as usize]
-        {
-            self.cpu_running = false;
-            self.message_log.log(Message::new(
-                MessageType::Info,
-                format!("Reached breakpoint at {:#X}", self.cpu.pcounter),
-            ))
-        }
     }
 
     pub fn run(&mut self) {
         loop {
             self.draw();
 
+            let executed = self.cpu.executed;
             self.update_cpu();
+            if self.cpu.executed != executed {
+                self.watch_list.step();
+                self.follow_ram_write();
+            }
+
+            self.reload_if_modified();
 
             self.handle_input();
 
@@ -376,16 +1197,8 @@ impl App {
                 true
             }
             KeyCode::F(2) => {
-                let path_opt = self.prompt::<PathBuf>("Enter file path:");
-
-                match path_opt {
-                    None => {}
-                    Some(path) => match path.to_str() {
-                        None => {}
-                        Some(path_str) => {
-                            self.try_load_program(String::from(path_str));
-                        }
-                    },
+                if let Some(path) = self.browse_file() {
+                    self.try_load_program(path);
                 }
                 true
             }
@@ -406,13 +1219,45 @@ impl App {
                 true
             }
             KeyCode::F(5) => {
-                self.cpu_running = !self.cpu_running;
+                // Interrupt running towards a target, otherwise toggle free running
+                match self.run_target.take() {
+                    Some(target) => self.message_log.log(Message::new(
+                        MessageType::Info,
+                        format!("Interrupted running until {}", target.describe()),
+                    )),
+                    None => self.cpu_running = !self.cpu_running,
+                }
                 true
             }
             KeyCode::F(6) => {
                 self.cpu_step_requested = true;
                 true
             }
+            KeyCode::F(7) => {
+                if let Some(n) = self.prompt::<u32>("Number of instructions to step:") {
+                    if n > 0 {
+                        self.start_run_target(RunTarget::Steps(n));
+                    }
+                }
+                true
+            }
+            KeyCode::F(8) => {
+                let opt = self.prompt::<String>("Run until condition, e.g. 'reg2 == 0 || carry':");
+                if let Some(text) = opt {
+                    match Condition::parse_with(&text, &self.program.symbols) {
+                        Ok(condition) => self.start_run_target(RunTarget::Condition(condition)),
+                        Err(msg) => self.message_log.log(Message::new(
+                            MessageType::Error,
+                            format!("Invalid condition: {}", msg),
+                        )),
+                    }
+                }
+                true
+            }
+            KeyCode::F(9) => {
+                self.step_over_loop();
+                true
+            }
             _ => false,
         }
     }
@@ -421,6 +1266,19 @@ impl App {
         // Event handling
         if event::poll(Duration::from_millis(20)).unwrap() {
             if let crossterm::event::Event::Key(key) = event::read().unwrap() {
+                // The console and RAM editing need keys which are otherwise handled
+                // globally (Tab, Esc), so they get to see keys first
+                let consumed = match self.ui_mode {
+                    UiMode::Console => self.handle_event_console(&key),
+                    UiMode::InspectRam if self.ram_widget_state.is_editing() => {
+                        self.handle_event_ram_edit(&key)
+                    }
+                    _ => false,
+                };
+                if consumed {
+                    return;
+                }
+
                 // General input (always applicable), these are handled by the below
                 // call and we return right away if the input was consumed
                 if self.handle_input_general(&key) {
@@ -432,6 +1290,8 @@ impl App {
                     UiMode::InspectTerminal => self.handle_event_terminal(key),
                     UiMode::InspectRam => self.handle_event_ram(key),
                     UiMode::InspectProgram => self.handle_event_program(key),
+                    UiMode::InspectRegisters => self.handle_event_registers(key),
+                    UiMode::Console => {}
                 }
             }
         }
@@ -449,21 +1309,337 @@ impl App {
             _ => {}
         }
     }
-    fn handle_event_ram(&mut self, key: KeyEvent) {
+    // Returns true if keyevent was used/consumend
+    fn handle_event_console(&mut self, key: &KeyEvent) -> bool {
         match key.code {
-            KeyCode::Up | KeyCode::Char('k') => self.ram_widget_state.scroll(-1),
-            KeyCode::Down | KeyCode::Char('j') => self.ram_widget_state.scroll(1),
-            KeyCode::PageUp | KeyCode::Char('K') => self.ram_widget_state.scroll(-16),
-            KeyCode::PageDown | KeyCode::Char('J') => self.ram_widget_state.scroll(16),
-            KeyCode::Char('g') => match self.prompt::<u32>("Go to RAM address: ") {
-                None => {}
-                Some(n) => self.ram_widget_state.goto_address(n),
+            KeyCode::Char(c) => self.console_state.input.push(c),
+            KeyCode::Backspace => {
+                self.console_state.input.pop();
+            }
+            KeyCode::Up => self.console_state.history_previous(),
+            KeyCode::Down => self.console_state.history_next(),
+            KeyCode::Enter => {
+                let line = self.console_state.submit();
+                if !line.trim().is_empty() {
+                    self.execute_command(&line);
+                }
+            }
+            // Without input, Tab switches the context as everywhere else
+            KeyCode::Tab if self.console_state.input.trim().is_empty() => return false,
+            KeyCode::Tab => match commands::complete(&self.console_state.input) {
+                Ok(completed) => self.console_state.input = completed,
+                Err(candidates) if candidates.len() > 1 => self
+                    .message_log
+                    .log(Message::new(MessageType::Info, candidates.join(" "))),
+                Err(_) => {}
             },
+            _ => return false,
+        }
+        true
+    }
+    fn handle_event_registers(&mut self, key: KeyEvent) {
+        match key.code {
+            KeyCode::Up | KeyCode::Char('k') => self.registers_widget_state.move_selection(-1, 0),
+            KeyCode::Down | KeyCode::Char('j') => self.registers_widget_state.move_selection(1, 0),
+            KeyCode::Left | KeyCode::Char('h') => self.registers_widget_state.move_selection(0, -1),
+            KeyCode::Right | KeyCode::Char('l') => self.registers_widget_state.move_selection(0, 1),
+            KeyCode::Char('x') | KeyCode::Char('v') => {
+                let state = &mut self.registers_widget_state;
+                state.format = state.format.next();
+            }
+            KeyCode::Char('w') => {
+                let opt = self.prompt_with(
+                    "Watch expression, e.g. 'reg2', '[buffer+1]:x', 'reg3:s' or 'buffer..buffer+7':",
+                    |text, app| {
+                        // Validate before the prompt closes
+                        WatchExpression::parse(text, &app.program.symbols)?;
+                        Ok(String::from(text))
+                    },
+                );
+                if let Some(spec) = opt {
+                    let result = self.add_watch(&spec);
+                    self.log_result(result);
+                }
+            }
+            KeyCode::Char('u') => {
+                if let Some(n) = self.prompt::<u32>("Remove watch expression number (0 for all):") {
+                    let result = self.remove_watch((n > 0).then_some(n as usize));
+                    self.log_result(result);
+                }
+            }
+            KeyCode::Char(' ') => {
+                // Quick toggle for flags
+                let field = self.registers_widget_state.selected();
+                if field.is_flag() {
+                    self.set_register_field(field, field.get(&self.cpu) ^ 1);
+                }
+            }
+            KeyCode::Enter | KeyCode::Char('e') => {
+                let field = self.registers_widget_state.selected();
+                let prompt_text = match field.is_flag() {
+                    true => format!("New value for {} (true/false or 1/0):", field.name()),
+                    false => format!(
+                        "New value for {} (e.g. 0x1f, 'a' or {} + 1):",
+                        field.name(),
+                        field.name()
+                    ),
+                };
+                let opt = self.prompt_with(&prompt_text, |text, app| {
+                    field.parse_value(text, &app.cpu, &app.program.symbols)
+                });
+                if let Some(value) = opt {
+                    self.set_register_field(field, value);
+                }
+            }
+            _ => {}
+        }
+    }
+
+    fn set_register_field(&mut self, field: RegisterField, value: u16) {
+        let old_value = field.get(&self.cpu);
+        field.set(&mut self.cpu, value);
+
+        let message = match field.is_flag() {
+            true => format!("Set {} to {}", field.name(), value != 0),
+            false => format!(
+                "Set {} from {:#06x} to {:#06x} ({})",
+                field.name(),
+                old_value,
+                value,
+                value
+            ),
+        };
+        self.message_log
+            .log(Message::new(MessageType::Info, message));
+    }
+
+    // Returns true if keyevent was used/consumend, any other key cancels editing
+    fn handle_event_ram_edit(&mut self, key: &KeyEvent) -> bool {
+        match key.code {
+            KeyCode::Char(c) if c.is_ascii_hexdigit() => self.ram_widget_state.push_edit_digit(c),
+            KeyCode::Backspace => {
+                if let Some(buffer) = self.ram_widget_state.edit_buffer.as_mut() {
+                    buffer.pop();
+                }
+            }
+            KeyCode::Esc => self.ram_widget_state.edit_buffer = None,
+            KeyCode::Enter => {
+                let address = self.ram_widget_state.cursor;
+                if let Some(value) = self.ram_widget_state.take_edit_value() {
+                    let old_value = self.cpu.ram[address as usize];
+                    self.cpu.ram[address as usize] = value;
+                    self.message_log.log(Message::new(
+                        MessageType::Info,
+                        format!(
+                            "Set {:#06x} from {:#06x} to {:#06x}",
+                            address, old_value, value
+                        ),
+                    ));
+                    self.ram_widget_state.move_cursor(1);
+                }
+            }
+            _ => {
+                self.ram_widget_state.edit_buffer = None;
+                return false;
+            }
+        }
+        true
+    }
+
+    // Parse input of the form `<start>..<end> <value>`, the range may use labels
+    // and the value is evaluated against the CPU state
+    fn parse_range_and_value(&self, text: &str) -> Result<((u16, u16), u16), String> {
+        match text.trim().rsplit_once(char::is_whitespace) {
+            Some((range, value)) => Ok((
+                parse_range_with(range, &self.program.symbols)?,
+                expression::evaluate(value, &self.cpu, &self.program.symbols)?,
+            )),
+            None => Err(format!("Expected a range and a value, got '{}'", text)),
+        }
+    }
+
+    fn log_result(&mut self, result: Result<String, String>) {
+        let message = match result {
+            Ok(msg) => Message::new(MessageType::Info, msg),
+            Err(msg) => Message::new(MessageType::Error, msg),
+        };
+        self.message_log.log(message);
+    }
+
+    fn handle_event_ram(&mut self, key: KeyEvent) {
+        match key.code {
+            KeyCode::Char(':') => self.ui_mode = UiMode::Console,
+            KeyCode::Up | KeyCode::Char('k') => self.ram_widget_state.move_rows(-1),
+            KeyCode::Down | KeyCode::Char('j') => self.ram_widget_state.move_rows(1),
+            KeyCode::Left | KeyCode::Char('h') => self.ram_widget_state.move_cursor(-1),
+            KeyCode::Right | KeyCode::Char('l') => self.ram_widget_state.move_cursor(1),
+            KeyCode::PageUp | KeyCode::Char('K') => self.ram_widget_state.move_rows(-16),
+            KeyCode::PageDown | KeyCode::Char('J') => self.ram_widget_state.move_rows(16),
+            KeyCode::Char('v') => self.ram_widget_state.cycle_format(),
+            KeyCode::Char('/') => self.prompt_search(
+                "ram",
+                "Search RAM for values or a string, e.g. '0x41, 0x42' or '\"Hello\"':",
+            ),
+            KeyCode::Char('n') => self.repeat_search(true, true),
+            KeyCode::Char('N') => self.repeat_search(true, false),
+            // Typing a hex digit starts editing the word under the cursor
+            KeyCode::Char(c @ ('0'..='9' | 'a'..='f')) => self.ram_widget_state.push_edit_digit(c),
+            KeyCode::Enter => self.ram_widget_state.edit_buffer = Some(String::new()),
+            KeyCode::Char('F') => {
+                let opt = self.prompt::<String>("Fill (start..end value):");
+                if let Some(text) = opt {
+                    let result =
+                        self.parse_range_and_value(&text)
+                            .and_then(|((start, end), value)| {
+                                self.cpu.fill_ram(start, end, value)?;
+                                Ok(format!(
+                                    "Filled {:#06x}..{:#06x} with {:#06x}",
+                                    start, end, value
+                                ))
+                            });
+                    self.log_result(result);
+                }
+            }
+            KeyCode::Char('C') => {
+                let opt = self.prompt::<String>("Copy (start..end destination):");
+                if let Some(text) = opt {
+                    let result =
+                        self.parse_range_and_value(&text)
+                            .and_then(|((start, end), dest)| {
+                                self.cpu.copy_ram(start, end, dest)?;
+                                Ok(format!(
+                                    "Copied {:#06x}..{:#06x} to {:#06x}",
+                                    start, end, dest
+                                ))
+                            });
+                    self.log_result(result);
+                }
+            }
+            KeyCode::Char('s') => {
+                let address = self.ram_widget_state.cursor;
+                let opt = self.prompt::<String>(&format!(
+                    "String to store at {:#06x}, one character per word, 0 terminated (escapes: \\n, \\t, \\\\):",
+                    address
+                ));
+                if let Some(text) = opt {
+                    let result = commands::parse_escapes(&text).and_then(|text| {
+                        let n = self.cpu.write_ram_string(address, &text)?;
+                        Ok(format!(
+                            "Stored {:?} at {:#06x} ({} words)",
+                            text, address, n
+                        ))
+                    });
+                    self.log_result(result);
+                }
+            }
+            KeyCode::Char('A') => {
+                self.ram_widget_state.follow_writes = !self.ram_widget_state.follow_writes;
+                self.message_log.log(Message::new(
+                    MessageType::Info,
+                    match self.ram_widget_state.follow_writes {
+                        true => String::from("RAM view follows writes of the program"),
+                        false => String::from("RAM view stays at the cursor"),
+                    },
+                ));
+            }
+            KeyCode::Char('g') => {
+                if let Some(address) =
+                    self.prompt_value("Go to RAM address, e.g. 0x20 or [reg2+4]:")
+                {
+                    self.ram_widget_state.goto_address(address as u32);
+                }
+            }
+            KeyCode::Char('w') => {
+                let opt = self.prompt::<String>("Watch (address[..end] [r|w|rw|w=value]):");
+                if let Some(spec) = opt {
+                    match Watchpoint::parse_with(&spec, &self.program.symbols) {
+                        Ok(watchpoint) => {
+                            self.message_log.log(Message::new(
+                                MessageType::Info,
+                                format!("Watchpoint {}", watchpoint.describe()),
+                            ));
+                            self.debugger.watchpoints.add(watchpoint);
+                        }
+                        Err(msg) => self.message_log.log(Message::new(
+                            MessageType::Error,
+                            format!("Invalid watchpoint: {}", msg),
+                        )),
+                    }
+                }
+            }
+            KeyCode::Char('x') => {
+                if let Some(address) = self.prompt_value("Remove watchpoints covering address:") {
+                    let n = self.debugger.watchpoints.remove_at(address);
+                    self.message_log.log(Message::new(
+                        MessageType::Info,
+                        format!("Removed {} watchpoint(s)", n),
+                    ));
+                }
+            }
+            KeyCode::Char('o') => {
+                let opt =
+                    self.prompt::<String>("Toggle IO break (write [char] | clear | empty | rng):");
+                if let Some(spec) = opt {
+                    match IoBreak::parse(&spec) {
+                        Ok(io_break) => {
+                            let is_set = self.debugger.toggle_io_break(io_break);
+                            self.message_log.log(Message::new(
+                                MessageType::Info,
+                                format!(
+                                    "IO break on {} {}",
+                                    io_break.describe(),
+                                    match is_set {
+                                        true => "set",
+                                        false => "removed",
+                                    }
+                                ),
+                            ));
+                        }
+                        Err(msg) => self.message_log.log(Message::new(
+                            MessageType::Error,
+                            format!("Invalid IO break: {}", msg),
+                        )),
+                    }
+                }
+            }
+            KeyCode::Char('W') => {
+                if self.debugger.watchpoints.is_empty() && self.debugger.io_breaks.is_empty() {
+                    self.message_log.log(Message::new(
+                        MessageType::Info,
+                        String::from("No watchpoints or IO breaks set"),
+                    ));
+                }
+                let descriptions: Vec<String> = self
+                    .debugger
+                    .watchpoints
+                    .iter()
+                    .map(|wp| wp.describe())
+                    .collect();
+                for description in descriptions {
+                    self.message_log.log(Message::new(
+                        MessageType::Info,
+                        format!("Watchpoint {}", description),
+                    ));
+                }
+                let descriptions: Vec<String> = self
+                    .debugger
+                    .io_breaks
+                    .iter()
+                    .map(|b| b.describe())
+                    .collect();
+                for description in descriptions {
+                    self.message_log.log(Message::new(
+                        MessageType::Info,
+                        format!("IO break on {}", description),
+                    ));
+                }
+            }
             _ => {}
         }
     }
     fn handle_event_program(&mut self, key: KeyEvent) {
         match key.code {
+            KeyCode::Char(':') => self.ui_mode = UiMode::Console,
             KeyCode::Up | KeyCode::Char('k') => self.pmem_widget_state.scroll(-1),
             KeyCode::Down | KeyCode::Char('j') => self.pmem_widget_state.scroll(1),
             KeyCode::PageUp | KeyCode::Char('K') => self.pmem_widget_state.scroll(-16),
@@ -471,21 +1647,260 @@ impl App {
             KeyCode::Char('f') => {
                 self.pmem_widget_state.focus_executing = !self.pmem_widget_state.focus_executing
             }
+            KeyCode::Char('m') => {
+                self.keep_ram_on_reload = !self.keep_ram_on_reload;
+                self.message_log.log(Message::new(
+                    MessageType::Info,
+                    match self.keep_ram_on_reload {
+                        true => String::from("RAM is kept when the program is reloaded"),
+                        false => String::from("RAM is cleared when the program is reloaded"),
+                    },
+                ));
+            }
+            KeyCode::Char('v') => match self.program.source {
+                Some(_) => self.pmem_widget_state.show_source = !self.pmem_widget_state.show_source,
+                None => self.message_log.log(Message::new(
+                    MessageType::Warning,
+                    String::from("No source available, load a .s file to debug on source level"),
+                )),
+            },
+            KeyCode::Enter => {
+                // Follow the selected jump, this stops following the executing instruction
+                let address = self.pmem_widget_state.selected as u16;
+                match self.program.jump_target(address, &self.cpu) {
+                    Some(target) => {
+                        self.pmem_widget_state.focus_executing = false;
+                        self.pmem_widget_state.selected = target as u32;
+                    }
+                    None => self.message_log.log(Message::new(
+                        MessageType::Warning,
+                        format!("No known jump target at {:#06x}", address),
+                    )),
+                }
+            }
+            KeyCode::Char('/') => self.prompt_search(
+                "program",
+                "Search instructions, e.g. 'st', '%reg5', 'add %reg1' or '0x0??68':",
+            ),
+            KeyCode::Char('n') => self.repeat_search(false, true),
+            KeyCode::Char('N') => self.repeat_search(false, false),
+            KeyCode::Char('g') => {
+                if let Some(address) = self.prompt_value("Go to program address, e.g. loop+2:") {
+                    self.pmem_widget_state.focus_executing = false;
+                    self.pmem_widget_state.selected = address as u32;
+                }
+            }
+            KeyCode::Char('l') if self.program.source.is_none() => {
+                self.message_log.log(Message::new(
+                    MessageType::Warning,
+                    String::from("No source available, load a .s file to debug on source level"),
+                ))
+            }
+            KeyCode::Char('l') => {
+                // Source lines are numbered from 1, like in editors
+                let opt = self.prompt_with("Go to source line:", |text, app| {
+                    let source = app.program.source.as_ref().unwrap();
+                    match u32::parse_prompt(text)? {
+                        0 => Err(String::from("Lines are numbered from 1")),
+                        line => source
+                            .address_of_line(line as usize - 1)
+                            .ok_or(format!("No instruction on or after line {}", line)),
+                    }
+                });
+                if let Some(address) = opt {
+                    self.pmem_widget_state.focus_executing = false;
+                    self.pmem_widget_state.selected = address as u32;
+                }
+            }
+            KeyCode::Char('r') => {
+                let address = self.pmem_widget_state.selected as u16;
+                self.start_run_target(RunTarget::Address(address));
+            }
             KeyCode::Char('b') => {
                 // Set a new breakpoint
-                let selected_is_breakpoint =
-                    self.program.breakpoints[self.pmem_widget_state.selected as usize];
-
-                self.program.breakpoints[self.pmem_widget_state.selected as usize] =
-                    !selected_is_breakpoint;
+                self.debugger
+                    .breakpoints
+                    .toggle(self.pmem_widget_state.selected as u16);
+            }
+            KeyCode::Char('c') => {
+                let address = self.pmem_widget_state.selected as u16;
+                let opt =
+                    self.prompt_with("Break condition (empty to clear):", |text, app| match text
+                        .trim()
+                    {
+                        "" => Ok(None),
+                        text => Condition::parse_with(text, &app.program.symbols).map(Some),
+                    });
+                if let Some(condition) = opt {
+                    self.debugger.breakpoints.insert(address).condition = condition;
+                    self.log_breakpoint(address);
+                }
+            }
+            KeyCode::Char('M') => {
+                let address = self.pmem_widget_state.selected as u16;
+                let opt =
+                    self.prompt_with("Bookmark name (empty to remove):", |text, _| {
+                        match text.trim() {
+                            "" => Ok(None),
+                            name => check_bookmark_name(name).map(|()| Some(String::from(name))),
+                        }
+                    });
+                if let Some(name) = opt {
+                    self.execute_debugger_command(Command::Bookmark { address, name });
+                }
+            }
+            KeyCode::Char(';') => {
+                let address = self.pmem_widget_state.selected as u16;
+                if let Some(text) = self.prompt::<String>("Comment (empty to remove):") {
+                    let text = Some(String::from(text.trim())).filter(|text| !text.is_empty());
+                    self.execute_debugger_command(Command::Comment { address, text });
+                }
+            }
+            KeyCode::Char('H') => self.branches_state.visible = !self.branches_state.visible,
+            KeyCode::Char(',') => self.goto_branch(true),
+            KeyCode::Char('.') => self.goto_branch(false),
+            KeyCode::Char(']') => self.goto_annotation(true),
+            KeyCode::Char('[') => self.goto_annotation(false),
+            KeyCode::Char('\'') => {
+                let opt = self.prompt_with("Go to bookmark:", |text, app| {
+                    app.debugger
+                        .annotations
+                        .find_bookmark(text.trim())
+                        .ok_or(format!("No bookmark '{}'", text.trim()))
+                });
+                if let Some(address) = opt {
+                    self.pmem_widget_state.focus_executing = false;
+                    self.pmem_widget_state.selected = address as u32;
+                }
+            }
+            KeyCode::Char('t') => {
+                let address = self.pmem_widget_state.selected as u16;
+                let opt = self.prompt::<String>(
+                    "Tracepoint message, e.g. 'i={reg2:x} sum={reg5}' (empty to stop instead):",
+                );
+                if let Some(text) = opt {
+                    let result = self.debugger.breakpoints.set_log_message(
+                        address,
+                        &text,
+                        &self.program.symbols,
+                    );
+                    match result {
+                        Ok(()) => self.log_breakpoint(address),
+                        Err(msg) => self.message_log.log(Message::new(
+                            MessageType::Error,
+                            format!("Invalid message: {}", msg),
+                        )),
+                    }
+                }
+            }
+            KeyCode::Char('i') => {
+                let address = self.pmem_widget_state.selected as u16;
+                if let Some(n) = self.prompt::<u32>("Ignore count:") {
+                    self.debugger.breakpoints.set_ignore_count(address, n);
+                    self.log_breakpoint(address);
+                }
+            }
+            KeyCode::Char('d') => {
+                let address = self.pmem_widget_state.selected as u16;
+                if let Some(bp) = self.debugger.breakpoints.get_mut(address) {
+                    bp.enabled = !bp.enabled;
+                    self.log_breakpoint(address);
+                }
+            }
+            KeyCode::Char('a') => {
+                let address = self.pmem_widget_state.selected as u16;
+                let opt = self.prompt::<String>(&format!(
+                    "Replace instruction at {:#06x} (assembly, e.g. 'add %reg1 %reg2 %reg3', or 5 digit hex word):",
+                    address
+                ));
+                if let Some(text) = opt {
+                    let old = self
+                        .program
+                        .operations
+                        .get(address as usize)
+                        .map(|op| op.get_assembly_string());
+                    let result = assembler::parse_word_at(&text, address, &self.program.symbols)
+                        .and_then(|word| {
+                            self.program.patch(address, word)?;
+                            Ok(format!(
+                                "Patched {:#06x}: {} -> {} ({:05x})",
+                                address,
+                                old.unwrap_or_default(),
+                                self.program.assembly_string(address, &self.cpu),
+                                word.buffer
+                            ))
+                        });
+                    self.log_result(result);
+                }
+            }
+            KeyCode::Char('s') => {
+                // The program may have been assembled from a source, never save over it
+                let default_path = self.program_path.as_ref().map(|path| {
+                    Path::new(path)
+                        .with_extension("hex")
+                        .to_string_lossy()
+                        .into_owned()
+                });
+                let prompt_text = match &default_path {
+                    Some(path) => format!("Save program to (empty for '{}'):", path),
+                    None => String::from("Save program to:"),
+                };
+                if let Some(text) = self.prompt::<String>(&prompt_text) {
+                    let path = match (text.trim(), default_path) {
+                        ("", Some(path)) => path,
+                        (path, _) => String::from(path),
+                    };
+                    if !path.ends_with(".hex") && Path::new(&path).exists() {
+                        let opt = self.prompt::<bool>(&format!(
+                            "'{}' is not a .hex file, overwrite it with hex words? (yes/no)",
+                            path
+                        ));
+                        if opt != Some(true) {
+                            return;
+                        }
+                    }
+                    self.save_program(path);
+                }
+            }
+            KeyCode::Char('B') => {
+                if self.debugger.breakpoints.is_empty() {
+                    self.message_log.log(Message::new(
+                        MessageType::Info,
+                        String::from("No breakpoints set"),
+                    ));
+                }
+                let descriptions: Vec<String> = self
+                    .debugger
+                    .breakpoints
+                    .iter()
+                    .map(|bp| bp.describe())
+                    .collect();
+                for description in descriptions {
+                    self.message_log.log(Message::new(
+                        MessageType::Info,
+                        format!("Breakpoint {}", description),
+                    ));
+                }
             }
             _ => {}
         }
     }
 
+    fn log_breakpoint(&mut self, address: u16) {
+        if let Some(bp) = self.debugger.breakpoints.get(address) {
+            let description = bp.describe();
+            self.message_log.log(Message::new(
+                MessageType::Info,
+                format!("Breakpoint {}", description),
+            ));
+        }
+    }
+
     pub fn reset(&mut self) {
         self.cpu = CpuState::default();
         self.program = Program::default();
+        self.program_path = None;
+        self.program_modified = None;
 
         self.registers_widget_state = RegistersDisplayState::default();
         self.ram_widget_state = RamTableState::default();
@@ -495,8 +1910,67 @@ impl App {
     }
 
     pub fn quit(&mut self) -> Result<(), Box<dyn Error>> {
+        self.save_session();
         disable_raw_mode()?;
         execute!(self.terminal.backend_mut(), LeaveAlternateScreen,)?;
         Ok(self.terminal.show_cursor()?)
     }
 }
+
+fn modification_time(path: &str) -> Option<SystemTime> {
+    fs::metadata(path)
+        .and_then(|metadata| metadata.modified())
+        .ok()
+}
+
+// The modification time of the file if it differs from the known one
+fn modified_since(path: &str, known: Option<SystemTime>) -> Option<SystemTime> {
+    modification_time(path).filter(|modified| Some(*modified) != known)
+}
+
+fn same_file(a: &str, b: &str) -> bool {
+    match (fs::canonicalize(a), fs::canonicalize(b)) {
+        (Ok(a), Ok(b)) => a == b,
+        _ => a == b,
+    }
+}
+
+// Save the program as a .hex file, returns the modification time of the written file
+fn write_program(path: &str, program: &Program) -> io::Result<Option<SystemTime>> {
+    backend::hex_parser::write_hexfile(String::from(path), &program.instruction_words)?;
+    Ok(modification_time(path))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn save_does_not_reload_test() {
+        let dir = std::env::temp_dir().join(format!("mpmp-emu-save-{}", std::process::id()));
+        fs::create_dir_all(&dir).unwrap();
+        let path = dir.join("prog.hex").to_string_lossy().into_owned();
+        let program = Program::from([0x00180u32, 0x0007fu32].as_slice());
+
+        // A file written by the user is reloaded, one saved by us is not
+        assert!(modified_since(&path, None).is_none());
+        fs::write(&path, "v3.0 hex words plain\n0007f\n").unwrap();
+        assert!(modified_since(&path, None).is_some());
+
+        let saved = write_program(&path, &program).unwrap();
+        assert!(saved.is_some());
+        assert_eq!(modified_since(&path, saved), None);
+        assert_eq!(Program::load(&path).unwrap().operations.len(), 2);
+
+        // Saving a copy next to the program does not replace the program file
+        let copy = dir.join("copy.hex").to_string_lossy().into_owned();
+        write_program(&copy, &program).unwrap();
+        assert!(!same_file(&path, &copy));
+        assert!(same_file(
+            &path,
+            &dir.join(".").join("prog.hex").to_string_lossy()
+        ));
+
+        fs::remove_dir_all(&dir).unwrap();
+    }
+}
@@ -0,0 +1,891 @@
+// The debugger command language used by the console, e.g. `break 0x10 if reg3 == 5`,
+// `step 10`, `print reg3`, `x/16 0x100` or `set reg2 = 5`
+
+use crate::backend::annotations::check_bookmark_name;
+use crate::backend::breakpoints::{BreakpointKind, Condition, MessageTemplate};
+use crate::backend::debugger::Debugger;
+use crate::backend::expression::{evaluate_constant, parse_number, Expr};
+use crate::backend::io_breaks::IoBreak;
+use crate::backend::runtime::{CpuState, RAM_SIZE};
+use crate::backend::search::Search;
+use crate::backend::symbols::SymbolTable;
+use crate::backend::watch_list::WatchExpression;
+use crate::backend::watchpoints::Watchpoint;
+
+// Names offered by tab completion
+pub const COMMAND_NAMES: [&str; 35] = [
+    "advance",
+    "assert",
+    "bookmark",
+    "break",
+    "clear",
+    "comment",
+    "condition",
+    "continue",
+    "delay",
+    "delete",
+    "disable",
+    "display",
+    "dump",
+    "enable",
+    "find",
+    "help",
+    "ignore",
+    "info",
+    "input",
+    "iobreak",
+    "load",
+    "over",
+    "print",
+    "reset",
+    "set",
+    "source",
+    "step",
+    "symbols",
+    "trace",
+    "undisplay",
+    "unwatch",
+    "until",
+    "view",
+    "watch",
+    "x",
+];
+
+pub const HELP_TEXT: [&str; 32] = [
+    "break <addr> [if <cond>]: Set a (conditional) breakpoint",
+    "trace <addr> <message>: Set a tracepoint, e.g. trace 0x5 i={reg2:x}",
+    "condition <addr> [<cond>]: Change or remove the condition of a breakpoint",
+    "ignore <addr> <n>: Skip the next n hits of a breakpoint",
+    "enable <addr>, disable <addr>: Enable or disable a breakpoint",
+    "delete [<addr>]: Delete the breakpoint at addr, or all breakpoints",
+    "watch <addr>[..<end>] [r|w|rw|w=<value>]: Set a watchpoint",
+    "unwatch <addr>: Remove watchpoints covering addr",
+    "iobreak write [<char>] | clear | empty | rng: Toggle an IO break",
+    "display <expr>[:x|b|c|d|s] | <start>..<end>: Add a watch expression, e.g. display buffer..buffer+7",
+    "undisplay [<n>]: Remove watch expression n (as numbered in the panel), or all",
+    "bookmark <addr> [<name>]: Set or remove a named bookmark in program memory",
+    "comment <addr> [<text>]: Set or remove the comment of an instruction",
+    "step [<n>]: Step n instructions (default 1)",
+    "continue: Start free running",
+    "until <cond>: Run until the condition holds",
+    "advance <addr>: Run until addr is reached",
+    "over: Step over the loop around the program counter",
+    "print[/x|/b|/c] <expr>: Print the value of an expression",
+    "x/<n> <addr>: Examine n RAM words starting at addr",
+    "set <reg|pc|flag|ram[addr]> = <expr>: Change a value",
+    "find ram <values>|\"<text>\" | program <pattern>: Search, e.g. find ram 0x41, 0x42 or find program st %reg5",
+    "info breakpoints|watchpoints|bookmarks|branches|registers: Show debugger state",
+    "dump: Show registers, flags and the terminal output",
+    "input <text>: Append text to the input buffer (escapes: \\n, \\t, \\\\)",
+    "assert <cond>: Fail if the condition does not hold, e.g. assert ram[0x10] == 0x41",
+    "source <path>: Execute the commands in a script file",
+    "load <path>, reset, delay <ms>: Load a program, reset the CPU, set the execution delay",
+    "symbols <path>: Load labels from a symbol file with 'name address' lines",
+    "view ram|program|source [<addr>] | registers | terminal: Switch context and scroll",
+    "clear: Clear the message log",
+    "help: Show this overview",
+];
+
+pub enum SetTarget {
+    Register(usize),
+    ProgramCounter,
+    Zero,
+    Carry,
+    Overflow,
+    Ram(Expr),
+}
+
+pub enum InfoTopic {
+    Breakpoints,
+    Watchpoints,
+    Bookmarks,
+    Branches,
+    Registers,
+}
+
+// Contexts which can be switched to with the view command, optionally
+// scrolled to an address. Without an address the program view follows the
+// executing instruction.
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+pub enum View {
+    Ram(Option<u16>),
+    Program(Option<u16>),
+    Source(Option<u16>),
+    Registers,
+    Terminal,
+}
+
+pub enum Command {
+    Break {
+        address: u16,
+        condition: Option<Condition>,
+    },
+    Trace {
+        address: u16,
+        message: MessageTemplate,
+    },
+    Condition {
+        address: u16,
+        condition: Option<Condition>,
+    },
+    Ignore {
+        address: u16,
+        count: u32,
+    },
+    Enable {
+        address: u16,
+        enabled: bool,
+    },
+    Delete(Option<u16>),
+    Bookmark {
+        address: u16,
+        name: Option<String>,
+    },
+    Comment {
+        address: u16,
+        text: Option<String>,
+    },
+    Watch(Watchpoint),
+    Unwatch(u16),
+    IoBreak(IoBreak),
+    Step(u32),
+    Continue,
+    Until(Condition),
+    Advance(u16),
+    Over,
+    Print {
+        expr: Expr,
+        format: char,
+    },
+    Examine {
+        count: u16,
+        address: u16,
+    },
+    Set {
+        target: SetTarget,
+        value: Expr,
+    },
+    Info(InfoTopic),
+    Dump,
+    Input(String),
+    Assert(Condition),
+    Source(String),
+    Load(String),
+    Symbols(String),
+    View(View),
+    Display(String),
+    Undisplay(Option<usize>),
+    Find(Search),
+    Reset,
+    Delay(u64),
+    Clear,
+    Help,
+}
+
+// Addresses are fixed when the command is entered, so they may use labels
+// but not registers, e.g. `break loop + 2`
+fn parse_address(text: &str, symbols: &SymbolTable) -> Result<u16, String> {
+    match text.trim() {
+        "" => Err(String::from("Missing address")),
+        text => evaluate_constant(text, symbols),
+    }
+}
+
+// Resolve `\n`, `\t` and `\\` in text given to the input command
+pub fn parse_escapes(text: &str) -> Result<String, String> {
+    let mut result = String::new();
+    let mut chars = text.chars();
+
+    while let Some(ch) = chars.next() {
+        match ch {
+            '\\' => match chars.next() {
+                Some('n') => result.push('\n'),
+                Some('t') => result.push('\t'),
+                Some('\\') => result.push('\\'),
+                Some(ch) => return Err(format!("Unknown escape sequence '\\{}'", ch)),
+                None => return Err(String::from("Incomplete escape sequence")),
+            },
+            ch => result.push(ch),
+        }
+    }
+
+    Ok(result)
+}
+
+// Either a register or flag name, or a RAM address like `ram[buffer + 2]` or `[reg3]`
+fn parse_set_target(text: &str, symbols: &SymbolTable) -> Result<SetTarget, String> {
+    // Labels in the address are case sensitive, the names are not
+    let lowercase = text.to_lowercase();
+    match lowercase.as_str() {
+        "pc" => Ok(SetTarget::ProgramCounter),
+        "zero" => Ok(SetTarget::Zero),
+        "carry" => Ok(SetTarget::Carry),
+        "overflow" => Ok(SetTarget::Overflow),
+        name => {
+            let prefix_len = match name {
+                _ if name.starts_with("ram[") => 4,
+                _ if name.starts_with('[') => 1,
+                _ => 0,
+            };
+            if prefix_len > 0 && name.ends_with(']') {
+                let address = &text[prefix_len..text.len() - 1];
+                return Ok(SetTarget::Ram(Expr::parse_with(address, symbols)?));
+            }
+            match name.strip_prefix("reg").map(str::parse::<usize>) {
+                Some(Ok(reg)) if reg < 8 => Ok(SetTarget::Register(reg)),
+                _ => Err(format!("Cannot set '{}'", text)),
+            }
+        }
+    }
+}
+
+impl Command {
+    pub fn parse(line: &str) -> Result<Command, String> {
+        Command::parse_with(line, &SymbolTable::default())
+    }
+
+    // Numbers in the command may be expressions using the labels of the program
+    pub fn parse_with(line: &str, symbols: &SymbolTable) -> Result<Command, String> {
+        let line = line.trim();
+        let (word, args) = match line.split_once(char::is_whitespace) {
+            Some((word, args)) => (word, args.trim()),
+            None => (line, ""),
+        };
+        // Commands like print and x take their format glued to the name
+        let (name, suffix) = match word.split_once('/') {
+            Some((name, suffix)) => (name, Some(suffix)),
+            None => (word, None),
+        };
+
+        match (name, suffix) {
+            ("break" | "b", None) => {
+                let (address, condition) = match args.split_once(" if ") {
+                    Some((address, condition)) => {
+                        (address, Some(Condition::parse_with(condition, symbols)?))
+                    }
+                    None => (args, None),
+                };
+                Ok(Command::Break {
+                    address: parse_address(address, symbols)?,
+                    condition,
+                })
+            }
+            ("trace", None) => match args.split_once(char::is_whitespace) {
+                Some((address, message)) => Ok(Command::Trace {
+                    address: parse_address(address, symbols)?,
+                    message: MessageTemplate::parse_with(message.trim(), symbols)?,
+                }),
+                None => Err(String::from("Usage: trace <addr> <message>")),
+            },
+            ("condition", None) => match args.split_once(char::is_whitespace) {
+                Some((address, condition)) => Ok(Command::Condition {
+                    address: parse_address(address, symbols)?,
+                    condition: Some(Condition::parse_with(condition.trim(), symbols)?),
+                }),
+                None => Ok(Command::Condition {
+                    address: parse_address(args, symbols)?,
+                    condition: None,
+                }),
+            },
+            ("ignore", None) => match args.split_once(char::is_whitespace) {
+                Some((address, count)) => match count.trim().parse::<u32>() {
+                    Ok(count) => Ok(Command::Ignore {
+                        address: parse_address(address, symbols)?,
+                        count,
+                    }),
+                    Err(_) => Err(format!("Invalid ignore count '{}'", count.trim())),
+                },
+                None => Err(String::from("Usage: ignore <addr> <n>")),
+            },
+            ("enable" | "disable", None) => Ok(Command::Enable {
+                address: parse_address(args, symbols)?,
+                enabled: name == "enable",
+            }),
+            ("delete" | "d", None) => match args {
+                "" => Ok(Command::Delete(None)),
+                address => Ok(Command::Delete(Some(parse_address(address, symbols)?))),
+            },
+            ("bookmark", None) => {
+                let (address, name) = args.split_once(char::is_whitespace).unwrap_or((args, ""));
+                let name = match name.trim() {
+                    "" => None,
+                    name => {
+                        check_bookmark_name(name)?;
+                        Some(String::from(name))
+                    }
+                };
+                Ok(Command::Bookmark {
+                    address: parse_address(address, symbols)?,
+                    name,
+                })
+            }
+            ("comment", None) => {
+                let (address, text) = args.split_once(char::is_whitespace).unwrap_or((args, ""));
+                Ok(Command::Comment {
+                    address: parse_address(address, symbols)?,
+                    text: Some(String::from(text.trim())).filter(|text| !text.is_empty()),
+                })
+            }
+            ("watch", None) => Ok(Command::Watch(Watchpoint::parse_with(args, symbols)?)),
+            ("unwatch", None) => Ok(Command::Unwatch(parse_address(args, symbols)?)),
+            ("iobreak", None) => Ok(Command::IoBreak(IoBreak::parse(args)?)),
+            ("step" | "s", None) => match args {
+                "" => Ok(Command::Step(1)),
+                n => match n.parse::<u32>() {
+                    Ok(n) if n > 0 => Ok(Command::Step(n)),
+                    _ => Err(format!("Invalid step count '{}'", n)),
+                },
+            },
+            ("continue" | "c", None) => Ok(Command::Continue),
+            ("until", None) => Ok(Command::Until(Condition::parse_with(args, symbols)?)),
+            ("advance", None) => Ok(Command::Advance(parse_address(args, symbols)?)),
+            ("over", None) => Ok(Command::Over),
+            ("print" | "p", format) => {
+                let format = match format {
+                    None | Some("d") => 'd',
+                    Some("x") => 'x',
+                    Some("b") => 'b',
+                    Some("c") => 'c',
+                    Some(f) => return Err(format!("Unknown format '{}'", f)),
+                };
+                Ok(Command::Print {
+                    expr: Expr::parse_with(args, symbols)?,
+                    format,
+                })
+            }
+            ("x", count) => {
+                let count = match count {
+                    None => 1,
+                    Some(n) => match parse_number(n)? {
+                        0 => return Err(String::from("Count must be at least 1")),
+                        n => n,
+                    },
+                };
+                Ok(Command::Examine {
+                    count,
+                    address: parse_address(args, symbols)?,
+                })
+            }
+            ("set", None) => match args.split_once('=') {
+                Some((target, value)) => Ok(Command::Set {
+                    target: parse_set_target(target.trim(), symbols)?,
+                    value: Expr::parse_with(value, symbols)?,
+                }),
+                None => Err(String::from("Usage: set <target> = <value>")),
+            },
+            ("info" | "i", None) => match args {
+                "breakpoints" | "break" | "b" => Ok(Command::Info(InfoTopic::Breakpoints)),
+                "watchpoints" | "watch" | "w" => Ok(Command::Info(InfoTopic::Watchpoints)),
+                "bookmarks" | "bookmark" => Ok(Command::Info(InfoTopic::Bookmarks)),
+                "branches" | "branch" => Ok(Command::Info(InfoTopic::Branches)),
+                "registers" | "reg" | "r" => Ok(Command::Info(InfoTopic::Registers)),
+                topic => Err(format!("Unknown info topic '{}'", topic)),
+            },
+            ("dump", None) => Ok(Command::Dump),
+            ("input", None) => Ok(Command::Input(parse_escapes(args)?)),
+            ("assert", None) => Ok(Command::Assert(Condition::parse_with(args, symbols)?)),
+            ("source", None) => match args {
+                "" => Err(String::from("Missing file path")),
+                path => Ok(Command::Source(String::from(path))),
+            },
+            ("load", None) => match args {
+                "" => Err(String::from("Missing file path")),
+                path => Ok(Command::Load(String::from(path))),
+            },
+            ("symbols", None) => match args {
+                "" => Err(String::from("Missing file path")),
+                path => Ok(Command::Symbols(String::from(path))),
+            },
+            ("view", None) => {
+                let (view, address) = match args.split_once(char::is_whitespace) {
+                    Some((view, address)) => (view, Some(parse_address(address, symbols)?)),
+                    None => (args, None),
+                };
+                match (view, address) {
+                    ("ram", address) => Ok(Command::View(View::Ram(address))),
+                    ("program", address) => Ok(Command::View(View::Program(address))),
+                    ("source", address) => Ok(Command::View(View::Source(address))),
+                    ("registers", None) => Ok(Command::View(View::Registers)),
+                    ("terminal", None) => Ok(Command::View(View::Terminal)),
+                    ("registers" | "terminal", Some(_)) => {
+                        Err(format!("The {} view takes no address", view))
+                    }
+                    _ => Err(format!("Unknown view '{}'", view)),
+                }
+            }
+            ("display", None) => {
+                // Validate the expression right away, it is parsed again when added
+                WatchExpression::parse(args, symbols)?;
+                Ok(Command::Display(String::from(args)))
+            }
+            ("undisplay", None) => match args {
+                "" => Ok(Command::Undisplay(None)),
+                n => match n.parse::<usize>() {
+                    Ok(n) if n > 0 => Ok(Command::Undisplay(Some(n))),
+                    _ => Err(format!("Invalid watch expression number '{}'", n)),
+                },
+            },
+            ("find", None) => Ok(Command::Find(Search::parse(args, symbols)?)),
+            ("reset", None) => Ok(Command::Reset),
+            ("delay", None) => match args.parse::<u64>() {
+                Ok(ms) => Ok(Command::Delay(ms)),
+                Err(_) => Err(format!("Invalid delay '{}'", args)),
+            },
+            ("clear", None) => Ok(Command::Clear),
+            ("help" | "h", None) => Ok(Command::Help),
+            ("", None) => Err(String::from("Empty command")),
+            _ => Err(format!("Unknown command '{}'", word)),
+        }
+    }
+
+    // Execute commands which only inspect or modify CPU and debugger state and
+    // return their output. Returns None for commands that control execution or
+    // the frontend, those have to be handled by the caller.
+    pub fn execute(
+        &self,
+        cpu: &mut CpuState,
+        debugger: &mut Debugger,
+    ) -> Option<Result<Vec<String>, String>> {
+        let output = match self {
+            Command::Break { address, condition } => {
+                // A tracepoint becomes a stopping breakpoint again, an existing
+                // condition is kept unless a new one is given
+                let breakpoint = debugger.breakpoints.insert(*address);
+                breakpoint.kind = BreakpointKind::Stop;
+                if condition.is_some() {
+                    breakpoint.condition = condition.clone();
+                }
+                vec![format!("Breakpoint {}", breakpoint.describe())]
+            }
+            Command::Trace { address, message } => {
+                let breakpoint = debugger.breakpoints.insert(*address);
+                breakpoint.kind = BreakpointKind::Log(message.clone());
+                vec![format!("Breakpoint {}", breakpoint.describe())]
+            }
+            Command::Condition { address, condition } => {
+                let breakpoint = debugger.breakpoints.insert(*address);
+                breakpoint.condition = condition.clone();
+                vec![format!("Breakpoint {}", breakpoint.describe())]
+            }
+            Command::Ignore { address, count } => {
+                let breakpoint = debugger.breakpoints.insert(*address);
+                breakpoint.ignore_count = *count;
+                vec![format!("Breakpoint {}", breakpoint.describe())]
+            }
+            Command::Enable { address, enabled } => match debugger.breakpoints.get_mut(*address) {
+                Some(breakpoint) => {
+                    breakpoint.enabled = *enabled;
+                    vec![format!("Breakpoint {}", breakpoint.describe())]
+                }
+                None => return Some(Err(format!("No breakpoint at {:#06x}", address))),
+            },
+            Command::Delete(Some(address)) => match debugger.breakpoints.remove(*address) {
+                true => vec![format!("Deleted breakpoint at {:#06x}", address)],
+                false => return Some(Err(format!("No breakpoint at {:#06x}", address))),
+            },
+            Command::Delete(None) => {
+                debugger.breakpoints.clear();
+                vec![String::from("Deleted all breakpoints")]
+            }
+            Command::Bookmark { address, name } => {
+                debugger.annotations.set_bookmark(*address, name.clone());
+                match name {
+                    Some(name) => vec![format!("Bookmark '{}' at {:#06x}", name, address)],
+                    None => vec![format!("Removed bookmark at {:#06x}", address)],
+                }
+            }
+            Command::Comment { address, text } => {
+                debugger.annotations.set_comment(*address, text.clone());
+                match text {
+                    Some(text) => vec![format!("Comment at {:#06x}: {}", address, text)],
+                    None => vec![format!("Removed comment at {:#06x}", address)],
+                }
+            }
+            Command::Watch(watchpoint) => {
+                let watchpoint = Watchpoint {
+                    hit_count: 0,
+                    ..*watchpoint
+                };
+                let description = format!("Watchpoint {}", watchpoint.describe());
+                debugger.watchpoints.add(watchpoint);
+                vec![description]
+            }
+            Command::Unwatch(address) => {
+                let n = debugger.watchpoints.remove_at(*address);
+                vec![format!("Removed {} watchpoint(s)", n)]
+            }
+            Command::IoBreak(io_break) => {
+                let state = match debugger.toggle_io_break(*io_break) {
+                    true => "set",
+                    false => "removed",
+                };
+                vec![format!("IO break on {} {}", io_break.describe(), state)]
+            }
+            Command::Print { expr, format } => {
+                let value = expr.evaluate(cpu);
+                vec![match format {
+                    'x' => format!("{:#06x}", value),
+                    'b' => format!("{:#018b}", value),
+                    'c' => format!("{:?}", char::from_u32(value as u32).unwrap_or('?')),
+                    _ => format!("{}", value),
+                }]
+            }
+            Command::Examine { count, address } => {
+                // Four words per line, just like the RAM table
+                let mut lines = Vec::new();
+                let end = (*address as usize + *count as usize).min(RAM_SIZE);
+                for line_start in (*address as usize..end).step_by(4) {
+                    let words: Vec<String> = (line_start..(line_start + 4).min(end))
+                        .map(|a| format!("{:04X}", cpu.ram[a]))
+                        .collect();
+                    lines.push(format!("{:04X}: {}", line_start, words.join(" ")));
+                }
+                if lines.is_empty() {
+                    return Some(Err(format!("Address {:#06x} is outside of RAM", address)));
+                }
+                lines
+            }
+            Command::Set { target, value } => {
+                let value = value.evaluate(cpu);
+                match target {
+                    SetTarget::Register(reg) => cpu.registers[*reg] = value,
+                    SetTarget::ProgramCounter => cpu.pcounter = value,
+                    SetTarget::Zero => cpu.flags.zero = value != 0,
+                    SetTarget::Carry => cpu.flags.carry = value != 0,
+                    SetTarget::Overflow => cpu.flags.overflow = value != 0,
+                    SetTarget::Ram(address) => {
+                        let address = address.evaluate(cpu) as usize;
+                        if address >= RAM_SIZE {
+                            return Some(Err(format!(
+                                "Address {:#06x} is outside of RAM",
+                                address
+                            )));
+                        }
+                        cpu.ram[address] = value;
+                    }
+                }
+                vec![format!("Set to {:#06x}", value)]
+            }
+            Command::Info(InfoTopic::Breakpoints) => {
+                let mut lines: Vec<String> = debugger
+                    .breakpoints
+                    .iter()
+                    .map(|bp| format!("Breakpoint {}", bp.describe()))
+                    .collect();
+                if lines.is_empty() {
+                    lines.push(String::from("No breakpoints set"));
+                }
+                lines
+            }
+            Command::Info(InfoTopic::Watchpoints) => {
+                let mut lines: Vec<String> = debugger
+                    .watchpoints
+                    .iter()
+                    .map(|wp| format!("Watchpoint {}", wp.describe()))
+                    .chain(
+                        debugger
+                            .io_breaks
+                            .iter()
+                            .map(|b| format!("IO break on {}", b.describe())),
+                    )
+                    .collect();
+                if lines.is_empty() {
+                    lines.push(String::from("No watchpoints or IO breaks set"));
+                }
+                lines
+            }
+            Command::Info(InfoTopic::Bookmarks) => {
+                let mut lines: Vec<String> = debugger
+                    .annotations
+                    .iter()
+                    .map(|(address, a)| format!("{:#06x} {}", address, a.describe()))
+                    .collect();
+                if lines.is_empty() {
+                    lines.push(String::from("No bookmarks or comments set"));
+                }
+                lines
+            }
+            Command::Info(InfoTopic::Branches) => {
+                let mut lines: Vec<String> = cpu
+                    .branches
+                    .iter()
+                    .map(|branch| format!("Jump {}", branch.describe()))
+                    .collect();
+                if lines.is_empty() {
+                    lines.push(String::from("No jumps taken since the last reset"));
+                }
+                lines
+            }
+            Command::Info(InfoTopic::Registers) => {
+                cpu.summary().lines().map(String::from).collect()
+            }
+            Command::Dump => {
+                let mut lines: Vec<String> = cpu.summary().lines().map(String::from).collect();
+                lines.push(format!("terminal: {:?}", cpu.ostream.string));
+                lines
+            }
+            Command::Input(text) => {
+                for ch in text.chars() {
+                    cpu.istream.append_char(ch);
+                }
+                vec![format!("Input buffer: {:?}", cpu.istream.string)]
+            }
+            Command::Assert(condition) => match condition.expr.is_true(cpu) {
+                true => vec![format!("Assertion holds: {}", condition.source)],
+                false => return Some(Err(format!("Assertion failed: {}", condition.source))),
+            },
+            Command::Help => HELP_TEXT.iter().map(|line| String::from(*line)).collect(),
+            _ => return None,
+        };
+
+        Some(Ok(output))
+    }
+}
+
+// Complete the command name at the start of the input. Returns the completed
+// input, or all candidates if the prefix is ambiguous.
+pub fn complete(input: &str) -> Result<String, Vec<&'static str>> {
+    if input.contains(char::is_whitespace) {
+        return Err(Vec::new());
+    }
+
+    let candidates: Vec<&'static str> = COMMAND_NAMES
+        .iter()
+        .copied()
+        .filter(|name| name.starts_with(input))
+        .collect();
+
+    match candidates.as_slice() {
+        [name] => Ok(format!("{} ", name)),
+        _ => {
+            // Extend the input to the longest common prefix of all candidates
+            let common = candidates.iter().skip(1).fold(
+                candidates.first().map_or("", |c| *c),
+                |common, name| {
+                    let len = common
+                        .chars()
+                        .zip(name.chars())
+                        .take_while(|(a, b)| a == b)
+                        .count();
+                    &common[..len]
+                },
+            );
+            match common.len() > input.len() {
+                true => Ok(String::from(common)),
+                false => Err(candidates),
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parse_test() {
+        assert!(matches!(
+            Command::parse("view ram 0x100"),
+            Ok(Command::View(View::Ram(Some(0x100))))
+        ));
+        assert!(matches!(
+            Command::parse("view program"),
+            Ok(Command::View(View::Program(None)))
+        ));
+        assert!(Command::parse("view registers 0x1").is_err());
+        assert!(Command::parse("ignore 0x3 many").is_err());
+        assert!(matches!(
+            Command::parse("break 0x10 if reg3 == 5"),
+            Ok(Command::Break {
+                address: 0x10,
+                condition: Some(_)
+            })
+        ));
+        assert!(matches!(Command::parse("step 10"), Ok(Command::Step(10))));
+        assert!(matches!(Command::parse("s"), Ok(Command::Step(1))));
+        assert!(matches!(
+            Command::parse("x/16 0x100"),
+            Ok(Command::Examine {
+                count: 16,
+                address: 0x100
+            })
+        ));
+        assert!(matches!(
+            Command::parse("print/x reg3"),
+            Ok(Command::Print { format: 'x', .. })
+        ));
+        assert!(matches!(
+            Command::parse("set reg2 = 5"),
+            Ok(Command::Set {
+                target: SetTarget::Register(2),
+                ..
+            })
+        ));
+        assert!(matches!(
+            Command::parse("delete"),
+            Ok(Command::Delete(None))
+        ));
+        assert!(
+            matches!(Command::parse("load  prog.hex "), Ok(Command::Load(path)) if path == "prog.hex")
+        );
+        assert!(
+            matches!(Command::parse("symbols fib.sym"), Ok(Command::Symbols(path)) if path == "fib.sym")
+        );
+        assert!(Command::parse("symbols").is_err());
+
+        assert!(
+            matches!(Command::parse("input hi\\n"), Ok(Command::Input(text)) if text == "hi\n")
+        );
+        assert!(matches!(
+            Command::parse("assert reg1 == 2"),
+            Ok(Command::Assert(_))
+        ));
+
+        assert!(Command::parse("").is_err());
+        assert!(Command::parse("input \\q").is_err());
+        assert!(Command::parse("frobnicate").is_err());
+        assert!(Command::parse("break").is_err());
+        assert!(Command::parse("step 0").is_err());
+        assert_eq!(
+            Command::parse("x/0 0x100").err(),
+            Some(String::from("Count must be at least 1"))
+        );
+        assert!(Command::parse("set reg9 = 1").is_err());
+        assert!(Command::parse("print/q reg1").is_err());
+
+        // Addresses are constant expressions which may use labels
+        let symbols = SymbolTable::parse("loop 0x3\nBuffer 0x100").unwrap();
+        assert!(matches!(
+            Command::parse_with("break loop + 2 if reg1 == Buffer", &symbols),
+            Ok(Command::Break {
+                address: 0x5,
+                condition: Some(_)
+            })
+        ));
+        assert!(matches!(
+            Command::parse_with("view ram Buffer + 0x10 * 2", &symbols),
+            Ok(Command::View(View::Ram(Some(0x120))))
+        ));
+        assert!(matches!(
+            Command::parse_with("set [Buffer + reg1] = -1", &symbols),
+            Ok(Command::Set {
+                target: SetTarget::Ram(_),
+                ..
+            })
+        ));
+        assert!(Command::parse_with("advance reg1", &symbols).is_err());
+        assert!(Command::parse("break loop").is_err());
+
+        assert!(
+            matches!(Command::parse_with("display Buffer..Buffer+3", &symbols), Ok(Command::Display(spec)) if spec == "Buffer..Buffer+3")
+        );
+        assert!(Command::parse("display reg1:q").is_err());
+        assert!(matches!(
+            Command::parse("undisplay 2"),
+            Ok(Command::Undisplay(Some(2)))
+        ));
+        assert!(Command::parse("undisplay 0").is_err());
+
+        assert!(matches!(
+            Command::parse_with("find ram Buffer, 0", &symbols),
+            Ok(Command::Find(Search::Ram(words))) if words == [0x100, 0]
+        ));
+        assert!(matches!(
+            Command::parse("find program st %reg5"),
+            Ok(Command::Find(Search::Program(_)))
+        ));
+        assert!(Command::parse("find registers 5").is_err());
+
+        assert!(matches!(
+            Command::parse_with("bookmark loop main_loop", &symbols),
+            Ok(Command::Bookmark { address: 0x3, name: Some(name) }) if name == "main_loop"
+        ));
+        assert!(matches!(
+            Command::parse("bookmark 0x3"),
+            Ok(Command::Bookmark {
+                address: 0x3,
+                name: None
+            })
+        ));
+        assert!(matches!(
+            Command::parse("comment 0x3 sum up  the row "),
+            Ok(Command::Comment { address: 0x3, text: Some(text) }) if text == "sum up  the row"
+        ));
+    }
+
+    #[test]
+    fn execute_test() {
+        let mut cpu = CpuState::default();
+        let mut debugger = Debugger::default();
+
+        let mut run = |line: &str, cpu: &mut CpuState| {
+            Command::parse(line)
+                .unwrap()
+                .execute(cpu, &mut debugger)
+                .unwrap()
+        };
+
+        run("set reg2 = 5", &mut cpu).unwrap();
+        assert_eq!(cpu.registers[2], 5);
+        run("set ram[reg2] = reg2 == 5", &mut cpu).unwrap();
+        assert_eq!(cpu.ram[5], 1);
+        run("set carry = 1", &mut cpu).unwrap();
+        assert!(cpu.flags.carry);
+
+        assert_eq!(
+            run("print/x reg2", &mut cpu),
+            Ok(vec![String::from("0x0005")])
+        );
+        assert_eq!(
+            run("x/6 0x4", &mut cpu),
+            Ok(vec![
+                String::from("0004: 0000 0001 0000 0000"),
+                String::from("0008: 0000 0000")
+            ])
+        );
+
+        assert!(run("assert reg2 == 5 && carry", &mut cpu).is_ok());
+        assert!(run("assert reg2 == 4", &mut cpu).is_err());
+        run("input ab", &mut cpu).unwrap();
+        assert_eq!(cpu.istream.string, "ab");
+
+        run("break 0x3 if reg2 == 5", &mut cpu).unwrap();
+        assert!(run("delete 0x4", &mut cpu).is_err());
+        assert_eq!(
+            run("ignore 0x3 2", &mut cpu),
+            Ok(vec![String::from(
+                "Breakpoint 0x0003 if reg2 == 5, ignore 2, hits 0"
+            )])
+        );
+        run("condition 0x3", &mut cpu).unwrap();
+        run("disable 0x3", &mut cpu).unwrap();
+        assert_eq!(
+            run("info breakpoints", &mut cpu),
+            Ok(vec![String::from(
+                "Breakpoint 0x0003, ignore 2, hits 0 (disabled)"
+            )])
+        );
+        assert!(run("enable 0x4", &mut cpu).is_err());
+        run("delete 0x3", &mut cpu).unwrap();
+
+        run("trace 0x5 i={reg2}", &mut cpu).unwrap();
+        run("condition 0x5 carry", &mut cpu).unwrap();
+        assert_eq!(
+            run("break 0x5", &mut cpu),
+            Ok(vec![String::from("Breakpoint 0x0005 if carry, hits 0")])
+        );
+
+        assert!(Command::parse("step")
+            .unwrap()
+            .execute(&mut cpu, &mut Debugger::default())
+            .is_none());
+    }
+
+    #[test]
+    fn complete_test() {
+        assert_eq!(complete("bre"), Ok(String::from("break ")));
+        assert_eq!(complete("de"), Ok(String::from("del")));
+        assert_eq!(complete("del"), Err(vec!["delay", "delete"]));
+        assert_eq!(complete("zzz"), Err(Vec::new()));
+    }
+}
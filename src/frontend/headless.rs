@@ -0,0 +1,133 @@
+use std::io::{self, IsTerminal, Read, Write};
+
+use crate::backend::debugger::{Debugger, StopReason};
+use crate::backend::program::Program;
+use crate::backend::runtime::{CpuState, IoEvent};
+use crate::frontend::script::{Script, ScriptContext, ScriptOutput};
+
+// Runs a program without the TUI. Piped stdin is fed to the input stream and
+// everything written to the terminal goes to stdout, debugger output to stderr.
+pub struct Headless {
+    cpu: CpuState,
+    program: Program,
+    debugger: Debugger,
+
+    pub dump_on_debug: bool, // Print the CPU state whenever a dbg instruction is executed
+}
+
+impl Headless {
+    pub fn new() -> Self {
+        Headless {
+            cpu: CpuState::default(),
+            program: Program::default(),
+            debugger: Debugger::default(),
+            dump_on_debug: false,
+        }
+    }
+
+    pub fn try_load_program(&mut self, path: String) -> bool {
+        match Program::load(&path) {
+            Err(msg) => {
+                eprintln!("Failed to load '{}': {}", path, msg);
+                false
+            }
+            Ok(program) => {
+                self.cpu = CpuState::default();
+                self.program = program;
+                true
+            }
+        }
+    }
+
+    pub fn has_program(&self) -> bool {
+        !self.program.operations.is_empty()
+    }
+
+    fn read_piped_input(&mut self) {
+        if !io::stdin().is_terminal() {
+            let mut input = String::new();
+            if io::stdin().read_to_string(&mut input).is_ok() {
+                self.cpu.istream.string = input;
+            }
+        }
+    }
+
+    // Run until the CPU halts. Returns the process exit code.
+    pub fn run(&mut self) -> i32 {
+        self.read_piped_input();
+
+        let mut stdout = io::stdout();
+
+        loop {
+            if self.cpu.pcounter as usize >= self.program.operations.len() {
+                eprintln!(
+                    "Program counter {:#X} is outside of the program",
+                    self.cpu.pcounter
+                );
+                return 1;
+            }
+
+            let stop_reason = self.debugger.step(&mut self.cpu, &self.program);
+
+            if let Some(IoEvent::TerminalWrite(ch)) = self.cpu.last_io_event {
+                let _ = write!(stdout, "{}", ch);
+            }
+            for message in self.debugger.take_trace_messages() {
+                eprintln!("{}", message);
+            }
+
+            match stop_reason {
+                Some(StopReason::Halt) => break,
+                Some(reason @ StopReason::DebugInstruction { .. }) if self.dump_on_debug => {
+                    let _ = stdout.flush();
+                    eprintln!("{}\n{}", reason.describe(), self.cpu.summary());
+                }
+                Some(StopReason::DebugInstruction { .. }) => {}
+                Some(reason) => eprintln!("{}", reason.describe()),
+                None => {}
+            }
+        }
+
+        let _ = stdout.flush();
+        0
+    }
+
+    // Execute a script instead of running freely. Returns the process exit code,
+    // which is non-zero if a command or assertion failed.
+    pub fn run_script(&mut self, path: &str) -> i32 {
+        let script = match Script::load(path) {
+            Ok(script) => script,
+            Err(msg) => {
+                eprintln!("{}", msg);
+                return 1;
+            }
+        };
+
+        self.read_piped_input();
+
+        let mut stdout = io::stdout();
+        let mut context = ScriptContext {
+            cpu: &mut self.cpu,
+            program: &mut self.program,
+            debugger: &mut self.debugger,
+        };
+        let result = script.run(&mut context, &mut |out| match out {
+            ScriptOutput::Terminal(ch) => {
+                let _ = write!(stdout, "{}", ch);
+            }
+            ScriptOutput::Message(message) => {
+                let _ = stdout.flush();
+                eprintln!("{}", message);
+            }
+        });
+        let _ = stdout.flush();
+
+        match result {
+            Ok(()) => 0,
+            Err(msg) => {
+                eprintln!("{}", msg);
+                1
+            }
+        }
+    }
+}
@@ -1,5 +1,10 @@
 mod app;
+mod commands;
+mod headless;
 mod log;
+mod script;
+mod session;
 mod widgets;
 
 pub use app::App;
+pub use headless::Headless;
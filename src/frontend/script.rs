@@ -0,0 +1,293 @@
+// Script files are plain lists of console commands, one per line. Empty lines
+// and lines starting with '#' are ignored. Unlike in the console, commands that
+// run the CPU (step, continue, until, advance, over) block until execution stops.
+// Script::run executes them headless, the TUI runs each line as a console command.
+
+use std::fs;
+
+use crate::backend::debugger::{Debugger, RunTarget};
+use crate::backend::program::Program;
+use crate::backend::runtime::{CpuState, IoEvent};
+use crate::backend::symbols::SymbolTable;
+use crate::frontend::commands::Command;
+
+// Upper bound for the instructions executed by a single command, so a script
+// cannot hang forever on a program that never stops
+pub const MAX_STEPS: u64 = 100_000_000;
+
+pub enum ScriptOutput {
+    Message(String),
+    Terminal(char), // Character written to the emulated terminal
+}
+
+pub struct Script {
+    path: String,
+    lines: Vec<(usize, String)>, // Line number and command
+}
+
+// Everything a script operates on
+pub struct ScriptContext<'a> {
+    pub cpu: &'a mut CpuState,
+    pub program: &'a mut Program,
+    pub debugger: &'a mut Debugger,
+}
+
+// Lines are parsed right before they are executed, so labels of a program or
+// symbol file loaded earlier in the script can be used
+pub fn parse_command(line: &str, symbols: &SymbolTable) -> Result<Command, String> {
+    match Command::parse_with(line, symbols)? {
+        Command::Source(_) => Err(String::from("Scripts cannot be nested")),
+        command => Ok(command),
+    }
+}
+
+impl Script {
+    pub fn load(path: &str) -> Result<Script, String> {
+        let text = fs::read_to_string(path).map_err(|err| format!("{}: {}", path, err))?;
+        Ok(Script::parse(path, &text))
+    }
+
+    pub fn parse(path: &str, text: &str) -> Script {
+        let lines = text
+            .lines()
+            .enumerate()
+            .map(|(index, line)| (index + 1, line.trim()))
+            .filter(|(_, line)| !line.is_empty() && !line.starts_with('#'))
+            .map(|(number, line)| (number, String::from(line)))
+            .collect();
+
+        Script {
+            path: String::from(path),
+            lines,
+        }
+    }
+
+    // Line numbers and commands, for frontends executing them on their own
+    pub fn lines(&self) -> impl Iterator<Item = (usize, &str)> {
+        self.lines
+            .iter()
+            .map(|(number, line)| (*number, line.as_str()))
+    }
+
+    // Execute all commands in order, stopping at the first failing one
+    pub fn run(
+        &self,
+        context: &mut ScriptContext,
+        output: &mut dyn FnMut(ScriptOutput),
+    ) -> Result<(), String> {
+        for (number, line) in self.lines.iter() {
+            parse_command(line, &context.program.symbols)
+                .and_then(|command| execute(&command, context, output))
+                .map_err(|msg| format!("{}:{}: {}", self.path, number, msg))?;
+        }
+
+        Ok(())
+    }
+}
+
+fn execute(
+    command: &Command,
+    context: &mut ScriptContext,
+    output: &mut dyn FnMut(ScriptOutput),
+) -> Result<(), String> {
+    match command {
+        Command::Step(n) => run(context, Some(RunTarget::Steps(*n)), output),
+        Command::Continue => run(context, None, output),
+        Command::Until(condition) => run(
+            context,
+            Some(RunTarget::Condition(condition.clone())),
+            output,
+        ),
+        Command::Advance(address) => run(context, Some(RunTarget::Address(*address)), output),
+        Command::Over => match context.program.enclosing_loop(context.cpu.pcounter) {
+            Some((start, end)) => run(context, Some(RunTarget::LeaveRange { start, end }), output),
+            None => Err(format!("No loop around {:#X}", context.cpu.pcounter)),
+        },
+        Command::Load(path) => {
+            *context.program =
+                Program::load(path).map_err(|msg| format!("Failed to load '{}': {}", path, msg))?;
+            *context.cpu = CpuState::default();
+            context.debugger.reset_hit_counts();
+            output(ScriptOutput::Message(format!("Loaded '{}'", path)));
+            Ok(())
+        }
+        Command::Symbols(path) => {
+            context.program.load_symbols(path)?;
+            output(ScriptOutput::Message(format!(
+                "Loaded {} symbols",
+                context.program.symbols.len()
+            )));
+            Ok(())
+        }
+        Command::Reset => {
+            *context.cpu = CpuState::default();
+            context.debugger.reset_hit_counts();
+            output(ScriptOutput::Message(String::from("Reset CPU")));
+            Ok(())
+        }
+        Command::Find(search) => {
+            output(ScriptOutput::Message(
+                search.summary(context.cpu, context.program),
+            ));
+            Ok(())
+        }
+        // Nothing to show without the interactive frontend
+        Command::Delay(_)
+        | Command::Clear
+        | Command::View(_)
+        | Command::Display(_)
+        | Command::Undisplay(_) => Ok(()),
+        command => match command.execute(context.cpu, context.debugger) {
+            Some(Ok(lines)) => {
+                for line in lines {
+                    output(ScriptOutput::Message(line));
+                }
+                Ok(())
+            }
+            Some(Err(msg)) => Err(msg),
+            None => Ok(()),
+        },
+    }
+}
+
+// Run until the debugger stops execution, the CPU halts or the target is reached
+fn run(
+    context: &mut ScriptContext,
+    mut target: Option<RunTarget>,
+    output: &mut dyn FnMut(ScriptOutput),
+) -> Result<(), String> {
+    if context.cpu.received_halt {
+        return Err(String::from("CPU is halted"));
+    }
+
+    for _ in 0..MAX_STEPS {
+        if context.cpu.pcounter as usize >= context.program.operations.len() {
+            return Err(format!(
+                "Program counter {:#X} is outside of the program",
+                context.cpu.pcounter
+            ));
+        }
+
+        let stop_reason = context.debugger.step(context.cpu, context.program);
+
+        if let Some(IoEvent::TerminalWrite(ch)) = context.cpu.last_io_event {
+            output(ScriptOutput::Terminal(ch));
+        }
+        for message in context.debugger.take_trace_messages() {
+            output(ScriptOutput::Message(message));
+        }
+
+        if let Some(reason) = stop_reason {
+            output(ScriptOutput::Message(reason.describe()));
+            return Ok(());
+        }
+
+        if let Some(target) = target.as_mut() {
+            if target.is_reached(context.cpu) {
+                output(ScriptOutput::Message(format!(
+                    "Stopped at {:#X} ({})",
+                    context.cpu.pcounter,
+                    target.describe()
+                )));
+                return Ok(());
+            }
+        }
+    }
+
+    Err(format!("Gave up after {} instructions", MAX_STEPS))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /*
+    ldc %reg1 0x8002
+    ld %reg0 %reg1
+    ldc %reg1 0x8000
+    st %reg1 %reg0
+    hlt
+    */
+    const PMEM_IO: [u32; 5] = [0x80092u32, 0x00869u32, 0x80090u32, 0x00868u32, 0x0007fu32];
+
+    fn run_script(text: &str) -> (Result<(), String>, String) {
+        let mut cpu = CpuState::default();
+        let mut program = Program::from(PMEM_IO.as_slice());
+        let mut debugger = Debugger::default();
+        let mut context = ScriptContext {
+            cpu: &mut cpu,
+            program: &mut program,
+            debugger: &mut debugger,
+        };
+
+        let mut terminal = String::new();
+        let script = Script::parse("test.cmds", text);
+        let result = script.run(&mut context, &mut |out| {
+            if let ScriptOutput::Terminal(ch) = out {
+                terminal.push(ch)
+            }
+        });
+
+        (result, terminal)
+    }
+
+    #[test]
+    fn run_test() {
+        let (result, terminal) = run_script(
+            "# Echo a single character\n\
+             input x\n\
+             break 0x3\n\
+             continue\n\
+             assert pc == 3 && reg0 == 0x78\n\
+             \n\
+             step\n\
+             assert reg0 == 0x78\n\
+             continue\n",
+        );
+        assert_eq!(result, Ok(()));
+        assert_eq!(terminal, "x");
+    }
+
+    #[test]
+    fn error_test() {
+        let (result, _) = run_script("step 2\nassert reg0 == 1\n");
+        assert_eq!(
+            result,
+            Err(String::from("test.cmds:2: Assertion failed: reg0 == 1"))
+        );
+
+        let (result, _) = run_script("step\nfrobnicate\n");
+        assert_eq!(
+            result,
+            Err(String::from("test.cmds:2: Unknown command 'frobnicate'"))
+        );
+
+        let (result, _) = run_script("continue\ncontinue\n");
+        assert_eq!(result, Err(String::from("test.cmds:2: CPU is halted")));
+    }
+
+    #[test]
+    fn symbols_test() {
+        let path = std::env::temp_dir().join(format!("mpmp-script-{}.sym", std::process::id()));
+        fs::write(&path, "echo 0x3\n").unwrap();
+
+        // Labels of a symbol file loaded by the script itself can be used
+        let (result, terminal) = run_script(&format!(
+            "symbols {}\n\
+             input x\n\
+             break echo\n\
+             continue\n\
+             assert pc == echo\n",
+            path.display()
+        ));
+        fs::remove_file(&path).unwrap();
+        assert_eq!(result, Ok(()));
+        assert_eq!(terminal, "");
+
+        let (result, _) = run_script("step\nsource other.cmds\n");
+        assert_eq!(
+            result,
+            Err(String::from("test.cmds:2: Scripts cannot be nested"))
+        );
+    }
+}
@@ -0,0 +1,235 @@
+// Sessions keep the debugger and view state of a program between runs. They are
+// stored as command scripts in the data directory, one per program path, and
+// executed again when the program is loaded.
+
+use std::env;
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+
+use crate::backend::breakpoints::BreakpointKind;
+use crate::backend::debugger::Debugger;
+use crate::backend::io_breaks::IoBreak;
+use crate::backend::watchpoints::{WatchKind, Watchpoint};
+
+// Number of entries kept in the list of recently loaded files
+const MAX_RECENT_FILES: usize = 10;
+
+// $XDG_DATA_HOME/mpmp-emu, falling back to ~/.local/share/mpmp-emu
+pub fn data_dir() -> Option<PathBuf> {
+    let base = match env::var_os("XDG_DATA_HOME") {
+        Some(dir) if !dir.is_empty() => PathBuf::from(dir),
+        _ => PathBuf::from(env::var_os("HOME")?).join(".local/share"),
+    };
+    Some(base.join("mpmp-emu"))
+}
+
+// 64 bit FNV-1a, unlike the std hashers it is guaranteed to stay the same
+fn hash(text: &str) -> u64 {
+    text.bytes().fold(0xcbf29ce484222325, |hash, byte| {
+        (hash ^ byte as u64).wrapping_mul(0x100000001b3)
+    })
+}
+
+// The same program may be given by different relative paths
+fn absolute_path(path: &str) -> String {
+    fs::canonicalize(path)
+        .map(|path| path.to_string_lossy().into_owned())
+        .unwrap_or_else(|_| String::from(path))
+}
+
+pub fn session_path(program_path: &str) -> Option<PathBuf> {
+    let file_name = format!("{:016x}.cmds", hash(&absolute_path(program_path)));
+    Some(data_dir()?.join("sessions").join(file_name))
+}
+
+pub fn write_session(program_path: &str, commands: &[String]) -> io::Result<()> {
+    let path = session_path(program_path).ok_or(io::ErrorKind::NotFound)?;
+    if let Some(dir) = path.parent() {
+        fs::create_dir_all(dir)?;
+    }
+
+    let mut text = format!("# Session of {}\n", absolute_path(program_path));
+    for command in commands {
+        text += command;
+        text.push('\n');
+    }
+    fs::write(path, text)
+}
+
+// Commands of the session, None if there is no session for the program
+pub fn read_session(program_path: &str) -> Option<Vec<(usize, String)>> {
+    let text = fs::read_to_string(session_path(program_path)?).ok()?;
+    Some(
+        text.lines()
+            .enumerate()
+            .map(|(index, line)| (index + 1, line.trim()))
+            .filter(|(_, line)| !line.is_empty() && !line.starts_with('#'))
+            .map(|(line, command)| (line, String::from(command)))
+            .collect(),
+    )
+}
+
+fn watch_spec(watchpoint: &Watchpoint) -> String {
+    let range = match watchpoint.start == watchpoint.end {
+        true => format!("{:#06x}", watchpoint.start),
+        false => format!("{:#06x}..{:#06x}", watchpoint.start, watchpoint.end),
+    };
+    match watchpoint.kind {
+        WatchKind::Read => format!("{} r", range),
+        WatchKind::Write => format!("{} w", range),
+        WatchKind::ReadWrite => format!("{} rw", range),
+        WatchKind::WriteValue(value) => format!("{} w={:#06x}", range, value),
+    }
+}
+
+fn io_break_spec(io_break: &IoBreak) -> String {
+    match io_break {
+        IoBreak::TerminalWrite(None) => String::from("write"),
+        IoBreak::TerminalWrite(Some(ch)) => format!("write {:#x}", *ch as u32),
+        IoBreak::TerminalClear => String::from("clear"),
+        IoBreak::EmptyInputRead => String::from("empty"),
+        IoBreak::Rng => String::from("rng"),
+    }
+}
+
+// Console commands recreating all breakpoints, watchpoints, IO breaks,
+// bookmarks and comments
+pub fn debugger_commands(debugger: &Debugger) -> Vec<String> {
+    let mut commands = Vec::new();
+
+    for bp in debugger.breakpoints.iter() {
+        commands.push(match &bp.kind {
+            BreakpointKind::Stop => format!("break {:#06x}", bp.address),
+            BreakpointKind::Log(template) => {
+                format!("trace {:#06x} {}", bp.address, template.source)
+            }
+        });
+        if let Some(condition) = &bp.condition {
+            commands.push(format!(
+                "condition {:#06x} {}",
+                bp.address, condition.source
+            ));
+        }
+        if bp.ignore_count > 0 {
+            commands.push(format!("ignore {:#06x} {}", bp.address, bp.ignore_count));
+        }
+        if !bp.enabled {
+            commands.push(format!("disable {:#06x}", bp.address));
+        }
+    }
+
+    for watchpoint in debugger.watchpoints.iter() {
+        commands.push(format!("watch {}", watch_spec(watchpoint)));
+    }
+    for io_break in debugger.io_breaks.iter() {
+        commands.push(format!("iobreak {}", io_break_spec(io_break)));
+    }
+    for (address, annotation) in debugger.annotations.iter() {
+        if let Some(name) = &annotation.bookmark {
+            commands.push(format!("bookmark {:#06x} {}", address, name));
+        }
+        if let Some(comment) = &annotation.comment {
+            commands.push(format!("comment {:#06x} {}", address, comment));
+        }
+    }
+
+    commands
+}
+
+fn recent_files_path() -> Option<PathBuf> {
+    Some(data_dir()?.join("recent_files"))
+}
+
+// Recently loaded files, the most recent one first
+pub fn recent_files() -> Vec<String> {
+    recent_files_path()
+        .and_then(|path| fs::read_to_string(path).ok())
+        .map(|text| text.lines().map(String::from).collect())
+        .unwrap_or_default()
+}
+
+pub fn add_recent_file(program_path: &str) -> io::Result<()> {
+    let path = recent_files_path().ok_or(io::ErrorKind::NotFound)?;
+    let program_path = absolute_path(program_path);
+
+    let mut files = recent_files();
+    files.retain(|file| *file != program_path && Path::new(file).exists());
+    files.insert(0, program_path);
+    files.truncate(MAX_RECENT_FILES);
+
+    if let Some(dir) = path.parent() {
+        fs::create_dir_all(dir)?;
+    }
+    fs::write(path, files.join("\n") + "\n")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::backend::runtime::CpuState;
+    use crate::frontend::commands::Command;
+
+    #[test]
+    fn debugger_commands_test() {
+        let mut cpu = CpuState::default();
+        let mut debugger = Debugger::default();
+        for line in [
+            "break 0x3",
+            "trace 0x5 i={reg2:x}",
+            "condition 0x5 reg2 > 1",
+            "ignore 0x3 4",
+            "disable 0x3",
+            "watch 0x10..0x1f rw",
+            "watch 0x20 w=5",
+            "iobreak write A",
+            "iobreak rng",
+            "comment 0x3 inner loop",
+            "bookmark 0x3 loop",
+        ] {
+            Command::parse(line)
+                .unwrap()
+                .execute(&mut cpu, &mut debugger);
+        }
+
+        let commands = debugger_commands(&debugger);
+        assert_eq!(
+            commands,
+            [
+                "break 0x0003",
+                "ignore 0x0003 4",
+                "disable 0x0003",
+                "trace 0x0005 i={reg2:x}",
+                "condition 0x0005 reg2 > 1",
+                "watch 0x0010..0x001f rw",
+                "watch 0x0020 w=0x0005",
+                "iobreak write 0x41",
+                "iobreak rng",
+                "bookmark 0x0003 loop",
+                "comment 0x0003 inner loop",
+            ]
+        );
+
+        // Executing the commands again gives the same state
+        let mut restored = Debugger::default();
+        for line in commands.iter() {
+            Command::parse(line)
+                .unwrap()
+                .execute(&mut cpu, &mut restored);
+        }
+        assert_eq!(debugger_commands(&restored), commands);
+    }
+
+    #[test]
+    fn session_path_test() {
+        assert_eq!(hash(""), 0xcbf29ce484222325);
+        assert_eq!(
+            session_path("/no/such/prog.hex"),
+            session_path("/no/such/prog.hex")
+        );
+        assert_ne!(
+            session_path("/no/such/prog.hex"),
+            session_path("/no/such/other.hex")
+        );
+    }
+}
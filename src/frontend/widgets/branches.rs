@@ -0,0 +1,107 @@
+use ratatui::prelude::{Alignment, Buffer, Constraint, Rect};
+use ratatui::style::{Color, Stylize};
+use ratatui::widgets::{Block, BorderType, Borders, Cell, Row, StatefulWidget, Table, Widget};
+
+use crate::backend::program::Program;
+use crate::backend::runtime::{Branch, BranchHistory};
+
+// The recently taken jumps, the most recent one first
+pub struct BranchesWidget<'a> {
+    history: &'a BranchHistory,
+    program: &'a Program,
+}
+
+#[derive(Default)]
+pub struct BranchesState {
+    pub visible: bool,
+    selected: Option<u64>, // Step of the selected jump, so it stays selected while running
+}
+
+impl BranchesState {
+    // Select the next older (or newer) jump, starting with the most recent one
+    pub fn select(&mut self, history: &BranchHistory, older: bool) -> Option<Branch> {
+        let index = self
+            .selected
+            .and_then(|step| history.iter().position(|b| b.step == step));
+        let index = match (index, older) {
+            (None, _) => 0,
+            (Some(i), true) => (i + 1).min(history.len().saturating_sub(1)),
+            (Some(i), false) => i.saturating_sub(1),
+        };
+
+        let branch = *history.iter().nth(index)?;
+        self.selected = Some(branch.step);
+        Some(branch)
+    }
+}
+
+impl<'a> BranchesWidget<'a> {
+    pub fn new(history: &'a BranchHistory, program: &'a Program) -> Self {
+        BranchesWidget { history, program }
+    }
+
+    fn mnemonic(&self, address: u16) -> &str {
+        self.program
+            .operations
+            .get(address as usize)
+            .map_or("", |op| op.get_assembly_opname())
+    }
+}
+
+impl<'a> StatefulWidget for BranchesWidget<'a> {
+    type State = BranchesState;
+
+    fn render(self, area: Rect, buf: &mut Buffer, state: &mut Self::State) {
+        let mut focus_row = 0;
+        let rows: Vec<Row> = self
+            .history
+            .iter()
+            .enumerate()
+            .map(|(i, branch)| {
+                let label = self.program.symbols.label_at(branch.target).unwrap_or("");
+                let row = Row::new(vec![
+                    Cell::from(format!("{}", i + 1)).fg(Color::DarkGray),
+                    Cell::from(format!("{:04x}", branch.source)).fg(Color::LightMagenta),
+                    Cell::from(self.mnemonic(branch.source)),
+                    Cell::from(format!("{:04x}", branch.target)).fg(Color::LightMagenta),
+                    Cell::from(label).fg(Color::Yellow),
+                    Cell::from(branch.flag_letters()).fg(Color::Green),
+                ]);
+                match state.selected == Some(branch.step) {
+                    true => {
+                        focus_row = i;
+                        row.reversed()
+                    }
+                    false => row,
+                }
+            })
+            .collect();
+
+        // Keep the selected jump visible
+        let visible_rows = area.height.saturating_sub(2) as usize;
+        let skip = (focus_row + 1).saturating_sub(visible_rows);
+
+        let table = Table::new(rows.into_iter().skip(skip))
+            .block(
+                Block::default()
+                    .title(" Recent branches ")
+                    .title_alignment(Alignment::Center)
+                    .borders(Borders::ALL)
+                    .border_type(BorderType::Plain),
+            )
+            .column_spacing(1)
+            .widths(
+                [
+                    Constraint::Length(2),
+                    Constraint::Length(4),
+                    Constraint::Length(5),
+                    Constraint::Length(4),
+                    Constraint::Min(4),
+                    Constraint::Length(3),
+                ]
+                .as_ref(),
+            );
+
+        Widget::render(table, area, buf)
+    }
+}
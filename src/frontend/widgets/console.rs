@@ -0,0 +1,116 @@
+use ratatui::prelude::{Alignment, Buffer, Rect};
+use ratatui::style::{Style, Stylize};
+use ratatui::text::{Line, Span};
+use ratatui::widgets::{Block, BorderType, Borders, Paragraph, StatefulWidget, Widget};
+
+#[derive(Default)]
+pub struct ConsoleWidget {}
+
+#[derive(Default)]
+pub struct ConsoleState {
+    pub input: String,
+    pub is_focussed: bool,
+
+    history: Vec<String>,
+    history_index: Option<usize>, // Entry shown while browsing the history
+}
+
+impl ConsoleState {
+    // Take the current input and remember it in the history
+    pub fn submit(&mut self) -> String {
+        let line = std::mem::take(&mut self.input);
+        self.history_index = None;
+
+        if !line.trim().is_empty() && self.history.last() != Some(&line) {
+            self.history.push(line.clone());
+        }
+
+        line
+    }
+
+    pub fn history_previous(&mut self) {
+        let index = match self.history_index {
+            Some(index) => index.saturating_sub(1),
+            None if self.history.is_empty() => return,
+            None => self.history.len() - 1,
+        };
+
+        self.history_index = Some(index);
+        self.input = self.history[index].clone();
+    }
+
+    pub fn history_next(&mut self) {
+        match self.history_index {
+            Some(index) if index + 1 < self.history.len() => {
+                self.history_index = Some(index + 1);
+                self.input = self.history[index + 1].clone();
+            }
+            Some(_) => {
+                self.history_index = None;
+                self.input.clear();
+            }
+            None => {}
+        }
+    }
+}
+
+impl StatefulWidget for ConsoleWidget {
+    type State = ConsoleState;
+
+    fn render(self, area: Rect, buf: &mut Buffer, state: &mut ConsoleState) {
+        let mut spans = vec![
+            Span::styled("> ", Style::default().light_blue()),
+            Span::raw(state.input.as_str()),
+        ];
+        if state.is_focussed {
+            spans.push(Span::styled(" ", Style::default().reversed()));
+        }
+
+        // Keep the end of long input visible
+        let width = area.width.saturating_sub(2) as usize;
+        let len = state.input.chars().count() + 3;
+        let scroll = len.saturating_sub(width) as u16;
+
+        let paragraph = Paragraph::new(Line::from(spans)).scroll((0, scroll)).block(
+            Block::new()
+                .title(" Console ")
+                .title_alignment(Alignment::Center)
+                .borders(Borders::ALL)
+                .border_type(match state.is_focussed {
+                    true => BorderType::Thick,
+                    false => BorderType::Plain,
+                }),
+        );
+
+        paragraph.render(area, buf);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn history_test() {
+        let mut state = ConsoleState::default();
+
+        state.history_previous();
+        assert_eq!(state.input, "");
+
+        for line in ["step", "print reg1", "print reg1", ""] {
+            state.input = String::from(line);
+            assert_eq!(state.submit(), line);
+        }
+
+        state.history_previous();
+        assert_eq!(state.input, "print reg1");
+        state.history_previous();
+        assert_eq!(state.input, "step");
+        state.history_previous();
+        assert_eq!(state.input, "step");
+        state.history_next();
+        assert_eq!(state.input, "print reg1");
+        state.history_next();
+        assert_eq!(state.input, "");
+    }
+}
@@ -0,0 +1,307 @@
+use std::env;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use ratatui::prelude::{Alignment, Buffer, Constraint, Direction, Layout, Rect};
+use ratatui::style::{Color, Style, Stylize};
+use ratatui::text::{Line, Span};
+use ratatui::widgets::{
+    Block, BorderType, Borders, Clear, List, ListItem, ListState, Paragraph, StatefulWidget, Widget,
+};
+
+// Only files the emulator can load are listed
+const EXTENSIONS: [&str; 2] = ["hex", "s"];
+
+#[derive(Debug, PartialEq, Eq)]
+struct FileEntry {
+    name: String,
+    is_dir: bool,
+}
+
+// Overlay for picking a program file, lists the directory of the typed path
+#[derive(Default)]
+pub struct FileBrowserWidget {}
+
+#[derive(Default)]
+pub struct FileBrowserState {
+    pub input: String,
+    pub error: Option<String>, // Shown until the input changes
+
+    entries: Vec<FileEntry>, // Entries of the typed directory matching the file name prefix
+    recent: Vec<String>,
+    selected: Option<usize>, // Index into the entries followed by the recent files
+}
+
+// Replace a leading ~ by the home directory
+fn expand_home(path: &str) -> PathBuf {
+    match (path.strip_prefix('~'), env::var_os("HOME")) {
+        (Some(rest), Some(home)) if rest.is_empty() || rest.starts_with('/') => {
+            PathBuf::from(home).join(rest.trim_start_matches('/'))
+        }
+        _ => PathBuf::from(path),
+    }
+}
+
+fn is_program_file(path: &Path) -> bool {
+    path.extension()
+        .and_then(|ext| ext.to_str())
+        .is_some_and(|ext| EXTENSIONS.contains(&ext))
+}
+
+impl FileBrowserState {
+    pub fn new(input: String, recent: Vec<String>) -> Self {
+        let mut state = FileBrowserState {
+            input,
+            recent,
+            ..Default::default()
+        };
+        state.refresh();
+        state
+    }
+
+    // Directory part (including the trailing '/') and file name part of the input
+    fn split_input(&self) -> (&str, &str) {
+        match self.input.rfind('/') {
+            Some(index) => self.input.split_at(index + 1),
+            None => ("", self.input.as_str()),
+        }
+    }
+
+    // List the directory of the input again
+    fn refresh(&mut self) {
+        let (dir, prefix) = self.split_input();
+        let dir_path = match dir {
+            "" => PathBuf::from("."),
+            dir => expand_home(dir),
+        };
+
+        let mut entries: Vec<FileEntry> = fs::read_dir(dir_path)
+            .map(|read_dir| {
+                read_dir
+                    .filter_map(|entry| entry.ok())
+                    .filter_map(|entry| {
+                        let name = entry.file_name().to_string_lossy().into_owned();
+                        let is_dir = entry.path().is_dir();
+                        let hidden = name.starts_with('.') && !prefix.starts_with('.');
+                        let listed = is_dir || is_program_file(&entry.path());
+                        (name.starts_with(prefix) && !hidden && listed)
+                            .then_some(FileEntry { name, is_dir })
+                    })
+                    .collect()
+            })
+            .unwrap_or_default();
+        entries.sort_by(|a, b| b.is_dir.cmp(&a.is_dir).then(a.name.cmp(&b.name)));
+
+        if prefix.is_empty() {
+            entries.insert(
+                0,
+                FileEntry {
+                    name: String::from(".."),
+                    is_dir: true,
+                },
+            );
+        }
+
+        self.entries = entries;
+        self.selected = None;
+    }
+
+    fn set_input(&mut self, input: String) {
+        self.input = input;
+        self.error = None;
+        self.refresh();
+    }
+
+    pub fn push(&mut self, ch: char) {
+        let mut input = std::mem::take(&mut self.input);
+        input.push(ch);
+        self.set_input(input);
+    }
+
+    pub fn pop(&mut self) {
+        let mut input = std::mem::take(&mut self.input);
+        input.pop();
+        self.set_input(input);
+    }
+
+    // Extend the file name to the longest prefix shared by all matching entries,
+    // a single matching directory is entered right away
+    pub fn complete(&mut self) {
+        let matching: Vec<&FileEntry> = self.entries.iter().filter(|e| e.name != "..").collect();
+        let Some(first) = matching.first() else {
+            return;
+        };
+
+        let mut common = first.name.clone();
+        for entry in matching.iter().skip(1) {
+            let len = common
+                .chars()
+                .zip(entry.name.chars())
+                .take_while(|(a, b)| a == b)
+                .count();
+            common = common.chars().take(len).collect();
+        }
+
+        let (dir, _) = self.split_input();
+        let mut input = format!("{}{}", dir, common);
+        if matching.len() == 1 && first.is_dir {
+            input.push('/');
+        }
+        self.set_input(input);
+    }
+
+    pub fn move_selection(&mut self, offset: i32) {
+        let len = self.entries.len() + self.recent.len();
+        if len == 0 {
+            return;
+        }
+
+        self.selected = Some(match self.selected {
+            Some(index) => (index as i32 + offset).clamp(0, len as i32 - 1) as usize,
+            None if offset < 0 => len - 1,
+            None => 0,
+        });
+    }
+
+    // Enter the selected or typed directory, or return the file to load
+    pub fn accept(&mut self) -> Option<String> {
+        let path = match self.selected {
+            Some(index) if index < self.entries.len() => {
+                let (dir, _) = self.split_input();
+                format!("{}{}", dir, self.entries[index].name)
+            }
+            Some(index) => self.recent[index - self.entries.len()].clone(),
+            None => self.input.clone(),
+        };
+
+        let expanded = expand_home(&path);
+        if expanded.is_dir() {
+            self.set_input(path.trim_end_matches('/').to_string() + "/");
+            None
+        } else if expanded.is_file() {
+            Some(expanded.to_string_lossy().into_owned())
+        } else {
+            self.error = Some(match path.is_empty() {
+                true => String::from("Enter a file path"),
+                false => format!("No such file: {}", path),
+            });
+            None
+        }
+    }
+}
+
+impl StatefulWidget for FileBrowserWidget {
+    type State = FileBrowserState;
+
+    fn render(self, area: Rect, buf: &mut Buffer, state: &mut FileBrowserState) {
+        let block = Block::default()
+            .title(" Load Program ")
+            .title_alignment(Alignment::Center)
+            .borders(Borders::ALL)
+            .border_type(BorderType::Double);
+        let inner = block.inner(area);
+        Clear.render(area, buf);
+        block.render(area, buf);
+
+        let recent_height = match state.recent.len() {
+            0 => 0,
+            n => n.min(5) as u16 + 1,
+        };
+        let chunks = Layout::default()
+            .direction(Direction::Vertical)
+            .constraints([
+                Constraint::Length(2),
+                Constraint::Min(1),
+                Constraint::Length(recent_height),
+            ])
+            .split(inner);
+
+        let status = match &state.error {
+            Some(error) => Line::styled(error.as_str(), Style::default().fg(Color::Red)),
+            None => Line::styled(
+                "Tab: complete, Up/Down: select, Enter: open, Esc: cancel",
+                Style::default().fg(Color::DarkGray),
+            ),
+        };
+        let input = Line::from(vec![
+            Span::styled("Path: ", Style::default().light_blue()),
+            Span::raw(state.input.as_str()),
+            Span::styled(" ", Style::default().reversed()),
+        ]);
+        Paragraph::new(vec![input, status]).render(chunks[0], buf);
+
+        let entries: Vec<ListItem> = state
+            .entries
+            .iter()
+            .map(|entry| match entry.is_dir {
+                true => ListItem::new(format!("{}/", entry.name)).fg(Color::LightBlue),
+                false => ListItem::new(entry.name.as_str()),
+            })
+            .collect();
+        let mut entries_state = ListState::default();
+        entries_state.select(state.selected.filter(|i| *i < state.entries.len()));
+        StatefulWidget::render(
+            List::new(entries).highlight_style(Style::default().reversed()),
+            chunks[1],
+            buf,
+            &mut entries_state,
+        );
+
+        let recent: Vec<ListItem> = state
+            .recent
+            .iter()
+            .map(|path| ListItem::new(path.as_str()))
+            .collect();
+        let mut recent_state = ListState::default();
+        recent_state.select(
+            state
+                .selected
+                .and_then(|i| i.checked_sub(state.entries.len())),
+        );
+        StatefulWidget::render(
+            List::new(recent)
+                .block(Block::default().title("Recent files").borders(Borders::TOP))
+                .highlight_style(Style::default().reversed()),
+            chunks[2],
+            buf,
+            &mut recent_state,
+        );
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn browse_test() {
+        let dir = env::temp_dir().join(format!("mpmp-emu-browse-{}", std::process::id()));
+        fs::create_dir_all(dir.join("programs")).unwrap();
+        for file in ["fib.hex", "fib.s", "notes.txt", "programs/echo.hex"] {
+            fs::write(dir.join(file), "").unwrap();
+        }
+        let root = format!("{}/", dir.to_string_lossy());
+
+        let mut state = FileBrowserState::new(root.clone(), Vec::new());
+        let names: Vec<&str> = state.entries.iter().map(|e| e.name.as_str()).collect();
+        assert_eq!(names, ["..", "programs", "fib.hex", "fib.s"]);
+
+        // Completion stops where the candidates differ
+        state.push('f');
+        state.complete();
+        assert_eq!(state.input, format!("{}fib.", root));
+        assert_eq!(state.accept(), None);
+        assert!(state.error.is_some());
+
+        // A single directory is entered
+        state.set_input(format!("{}pro", root));
+        state.complete();
+        assert_eq!(state.input, format!("{}programs/", root));
+
+        state.move_selection(1);
+        state.move_selection(1);
+        assert_eq!(state.accept(), Some(format!("{}programs/echo.hex", root)));
+
+        fs::remove_dir_all(dir).unwrap();
+    }
+}
@@ -17,25 +17,79 @@ impl Widget for HelpScreenWidget {
             Line::styled("General", Style::default().bold()),
             Line::from("Esc: Exit / Go back"),
             Line::from("F1: Display this screen"),
-            Line::from("F2: Load filepath"),
+            Line::from("F2: Load a program with the file browser (.hex, or .s sources which are assembled including their labels)"),
+            Line::from("    Tab completes the path, Up/Down select a listed or recently opened file"),
             Line::from("F3: Reset CPU"),
             Line::from("F4: Set per-instruction execution delay"),
-            Line::from("F5: Start/stop CPU"),
+            Line::from("F5: Start/stop CPU (also interrupts running until a target)"),
             Line::from("F6: Perform a single step"),
-            Line::from("Tab: Switch input context [Terminal Input Buffer / Program Memory / RAM]. The active context is highlighted."),
+            Line::from("F7: Step a given number of instructions"),
+            Line::from("F8: Run until a condition holds, e.g. 'reg2 == 0 || carry'"),
+            Line::from("F9: Step over loop (run until the program counter leaves the innermost loop around it)"),
+            Line::from("Tab: Switch input context [Terminal Input Buffer / Program Memory / Console / Registers / RAM]. The active context is highlighted."),
+            Line::from("Registers, flags and RAM words changed by the last step are shown in red, fading over the following steps"),
+            Line::from(""),
+            Line::styled("In prompts", Style::default().bold()),
+            Line::from("Left/Right/Home/End: Move the cursor, Up/Down: Previous inputs of the same prompt"),
+            Line::from("Numbers can be given in hex (0x1F), binary (0b101) or decimal, invalid input is explained below it"),
+            Line::from("Addresses and values are expressions: registers (reg3), pc, flags (zero, carry, overflow), labels, RAM ([reg2+4] or ram[...]),"),
+            Line::from("  arithmetic (+ - * / %), bit operations (& | ^ ~ << >>), comparisons and logic (== != < <= > >= && || !), e.g. 'buffer + reg1 * 2'"),
             Line::from(""),
             Line::styled("In Input Buffer context", Style::default().bold()),
             Line::from("All keystrokes are captured by the terminal."),
             Line::from(""),
+            Line::styled("In Registers context", Style::default().bold()),
+            Line::from("Arrow keys or h/j/k/l: Select register, flag or program counter"),
+            Line::from("Enter or e: Edit selected value, e.g. '0x1f', '31', 'reg2 + 1' or 'a' in single quotes"),
+            Line::from("Space: Toggle selected flag"),
+            Line::from("x or v: Switch the number format (hex, unsigned, signed, binary, ASCII)"),
+            Line::from("w: Add a watch expression, shown below the registers, e.g. 'reg2', '[buffer+1]:x' (formats: x, b, c, d), 'reg3:s' (0 terminated string) or 'buffer..buffer+7' (RAM range as string)"),
+            Line::from("u: Remove a watch expression by its number (0 for all). Values changed by the last step are highlighted"),
+            Line::from(""),
             Line::styled("In RAM context", Style::default().bold()),
-            Line::from("Down/Up or j/k: Navigate RAM table"),
-            Line::from("PGDOWN/PGUP or J/K: Navigate RAM table (16 steps)"),
-            Line::from("g: Go to specified memory address"),
+            Line::from("Arrow keys or h/j/k/l: Move cursor in RAM table"),
+            Line::from("PGDOWN/PGUP or J/K: Move cursor in RAM table (16 rows)"),
+            Line::from("g: Go to specified memory address, e.g. '[reg2+4]' (the address stored at reg2+4)"),
+            Line::from("v: Switch the number format (hex, unsigned, signed, binary, ASCII), the last column shows the words as characters"),
+            Line::from("/: Search values or a string, e.g. '0x41, 0x42' or '\"Hello\"'. n/N: Next/previous match"),
+            Line::from("A: Move the cursor to the address written by the last step (toggle)"),
+            Line::from("0-9, a-f or Enter: Edit word under cursor in hex, Enter to confirm, Esc to cancel"),
+            Line::from("F: Fill address range with a value, e.g. '0x100..0x10f 0'"),
+            Line::from("C: Copy address range to another address, e.g. '0x100..0x10f 0x200'"),
+            Line::from("s: Store a string at the cursor, one character per word followed by 0"),
+            Line::from("w: Add watchpoint, e.g. '0x10' (writes), '0x10..0x1f rw' (inclusive range, reads and writes), '0x20 r' or '0x20 w=0x5' (writes of a value)"),
+            Line::from("x: Remove watchpoints covering an address"),
+            Line::from("o: Toggle IO break: 'write' (any terminal output), 'write A' (a specific character), 'clear' (terminal cleared), 'empty' (read from empty input) or 'rng' (RNG accessed)"),
+            Line::from("W: List all watchpoints and IO breaks in the message log"),
             Line::from(""),
             Line::styled("In Program Memory context", Style::default().bold()),
             Line::from("f: Follow currently executing instruction (toggle)"),
+            Line::from("m: Keep RAM when the program file changes and is reloaded (toggle)"),
+            Line::from("v: Switch between assembly source and program memory (programs loaded from .s files)"),
             Line::from("Down/Up or j/k: Navigate program memory (when not following currently executing instruction)"),
+            Line::from("Enter: Select the target of the selected jump (register jumps only at the program counter)"),
+            Line::from("g: Go to specified instruction address, e.g. 'loop+2'"),
+            Line::from("l: Go to the instruction on (or below) a line of the assembly source"),
+            Line::from("/: Search instructions by mnemonic, register or raw word, e.g. 'st', '%reg5', 'add %reg1' or '0x0??68' (? for any digit). n/N: Next/previous match"),
+            Line::from("M: Set a named bookmark at the selected instruction (empty to remove), ': Go to a bookmark by name"),
+            Line::from(";: Comment the selected instruction (empty to remove). Bookmarks and comments are kept in the session"),
+            Line::from("]/[: Select the next/previous instruction with a bookmark or comment"),
+            Line::from("H: Show/hide the recent branches (the last 64 taken jumps with the flags they were taken on)"),
+            Line::from(",/.: Select the jump of the next older/newer recent branch"),
+            Line::from("r: Run to selected instruction"),
             Line::from("b: Toggle breakpoint at selected instruction"),
+            Line::from("c: Set break condition at selected instruction, e.g. 'reg3 == 0x10 && carry' or 'ram[0x20] != 0'"),
+            Line::from("t: Turn breakpoint at selected instruction into a tracepoint that logs a message instead of stopping, e.g. 'i={reg2:x} sum={reg5}' (formats: x, b, c, d)"),
+            Line::from("i: Set ignore count at selected instruction (number of hits to skip before stopping)"),
+            Line::from("d: Enable/disable breakpoint at selected instruction"),
+            Line::from("B: List all breakpoints in the message log"),
+            Line::from("a: Replace selected instruction with assembly, e.g. 'add %reg1 %reg2 %reg3' or 'jnzr loop', or a raw 5 digit hex word"),
+            Line::from("s: Save the (patched) program to a .hex file"),
+            Line::from(""),
+            Line::styled("In Console context (':' in RAM or Program Memory context)", Style::default().bold()),
+            Line::from("Enter: Execute command, type 'help' for a list of commands"),
+            Line::from("Up/Down: Browse command history"),
+            Line::from("Tab: Complete command name (switches the context if the input is empty), Shift+Tab leaves the console"),
         ];
 
         let paragraph = Paragraph::new(text).wrap(Wrap { trim: false }).block(
@@ -1,17 +1,28 @@
+mod branches;
+mod console;
+mod file_browser;
 mod keybuffer;
 mod log;
+mod number_format;
 mod pmem;
 mod prompt;
 mod ram;
 mod registers;
+mod source;
 mod terminal;
+mod watches;
 mod help_screen;
 
+pub use branches::{BranchesState, BranchesWidget};
+pub use console::{ConsoleState, ConsoleWidget};
+pub use file_browser::{FileBrowserState, FileBrowserWidget};
 pub use keybuffer::{KeybufferWidget, KeybufferWidgetState};
 pub use log::LogWidget;
 pub use pmem::{PmemTableState, PmemTableWidget};
-pub use prompt::PromptWidget;
+pub use prompt::{PromptState, PromptValue, PromptWidget};
 pub use ram::{RamTableState, RamTableWidget};
-pub use registers::{RegistersDisplayState, RegistersDisplayWidget};
+pub use registers::{RegisterField, RegistersDisplayState, RegistersDisplayWidget};
+pub use source::SourceWidget;
 pub use terminal::TerminalWidget;
+pub use watches::WatchesWidget;
 pub use help_screen::HelpScreenWidget;
@@ -0,0 +1,104 @@
+// How words are shown in the registers and RAM views, cycled with a key
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Default)]
+pub enum NumberFormat {
+    #[default]
+    Hex,
+    Unsigned,
+    Signed,
+    Binary,
+    Ascii,
+}
+
+impl NumberFormat {
+    pub fn next(self) -> NumberFormat {
+        match self {
+            NumberFormat::Hex => NumberFormat::Unsigned,
+            NumberFormat::Unsigned => NumberFormat::Signed,
+            NumberFormat::Signed => NumberFormat::Binary,
+            NumberFormat::Binary => NumberFormat::Ascii,
+            NumberFormat::Ascii => NumberFormat::Hex,
+        }
+    }
+
+    pub fn name(self) -> &'static str {
+        match self {
+            NumberFormat::Hex => "hex",
+            NumberFormat::Unsigned => "unsigned",
+            NumberFormat::Signed => "signed",
+            NumberFormat::Binary => "binary",
+            NumberFormat::Ascii => "ascii",
+        }
+    }
+
+    // Widest text produced by format()
+    pub fn width(self) -> u16 {
+        match self {
+            NumberFormat::Hex => 4,
+            NumberFormat::Unsigned => 5,
+            NumberFormat::Signed => 6,
+            NumberFormat::Binary => 16,
+            NumberFormat::Ascii => 4,
+        }
+    }
+
+    // Fixed width digits without prefix, words which are no printable character
+    // are shown as '.' in ASCII
+    pub fn format(self, value: u16) -> String {
+        match self {
+            NumberFormat::Hex => format!("{:04X}", value),
+            NumberFormat::Unsigned => format!("{}", value),
+            NumberFormat::Signed => format!("{}", value as i16),
+            NumberFormat::Binary => format!("{:016b}", value),
+            NumberFormat::Ascii => match value {
+                0 => String::from("'\\0'"),
+                0x09 => String::from("'\\t'"),
+                0x0A => String::from("'\\n'"),
+                0x0D => String::from("'\\r'"),
+                _ => format!("'{}'", ascii_char(value)),
+            },
+        }
+    }
+}
+
+// The character of a word for hex dumps, '.' if it isn't printable ASCII
+pub fn ascii_char(value: u16) -> char {
+    match value {
+        0x20..=0x7E => value as u8 as char,
+        _ => '.',
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn format_test() {
+        assert_eq!(NumberFormat::Hex.format(0x1F), "001F");
+        assert_eq!(NumberFormat::Unsigned.format(0xFFFF), "65535");
+        assert_eq!(NumberFormat::Signed.format(0xFFFF), "-1");
+        assert_eq!(NumberFormat::Signed.format(0x8000), "-32768");
+        assert_eq!(NumberFormat::Binary.format(5), "0000000000000101");
+        assert_eq!(NumberFormat::Ascii.format(0x41), "'A'");
+        assert_eq!(NumberFormat::Ascii.format(0x0A), "'\\n'");
+        assert_eq!(NumberFormat::Ascii.format(0x141), "'.'");
+        assert_eq!(ascii_char(0x7F), '.');
+
+        // Every format is reached once before the cycle starts over
+        let mut format = NumberFormat::default();
+        for _ in 0..4 {
+            format = format.next();
+            assert_ne!(format, NumberFormat::Hex);
+        }
+        assert_eq!(format.next(), NumberFormat::Hex);
+
+        for format in [
+            NumberFormat::Unsigned,
+            NumberFormat::Signed,
+            NumberFormat::Binary,
+        ] {
+            assert!(format.format(0x8000).len() <= format.width() as usize);
+        }
+    }
+}
@@ -1,13 +1,103 @@
+use crate::backend::annotations::Annotations;
+use crate::backend::breakpoints::BreakpointManager;
 use crate::backend::program::Program;
 use crate::backend::runtime::CpuState;
 
 use ratatui::prelude::{Alignment, Buffer, Color, Constraint, Rect};
 use ratatui::style::Stylize;
+use ratatui::text::{Line, Span};
 use ratatui::widgets::{Block, BorderType, Borders, Cell, Row, StatefulWidget, Table, Widget};
 
+// Number of branch arrows drawn side by side in the gutter
+const BRANCH_LANES: usize = 3;
+
 pub struct PmemTableWidget<'a> {
     program: &'a Program,
     program_counter: &'a u16,
+    cpu: &'a CpuState,
+    breakpoints: &'a BreakpointManager,
+    annotations: &'a Annotations,
+}
+
+// Jump drawn in the gutter, lane 0 is closest to the addresses
+#[derive(Debug, PartialEq, Eq)]
+struct BranchArrow {
+    source: u16,
+    target: u16,
+    lane: usize,
+}
+
+impl BranchArrow {
+    fn top(&self) -> u16 {
+        self.source.min(self.target)
+    }
+
+    fn bottom(&self) -> u16 {
+        self.source.max(self.target)
+    }
+}
+
+// Distribute the jumps onto the lanes, shorter jumps get the inner lanes.
+// Jumps which do not fit are left out.
+fn assign_lanes(mut jumps: Vec<(u16, u16)>) -> Vec<BranchArrow> {
+    jumps.sort_by_key(|(source, target)| source.abs_diff(*target));
+
+    let mut arrows: Vec<BranchArrow> = Vec::new();
+    for (source, target) in jumps {
+        let (top, bottom) = (source.min(target), source.max(target));
+        let free_lane = (0..BRANCH_LANES).find(|lane| {
+            !arrows
+                .iter()
+                .any(|a| a.lane == *lane && a.top() <= bottom && top <= a.bottom())
+        });
+        if let Some(lane) = free_lane {
+            arrows.push(BranchArrow {
+                source,
+                target,
+                lane,
+            });
+        }
+    }
+
+    arrows
+}
+
+// Draw the gutter for the instruction at the address, or for the label rows
+// above it. Jumps start with '─' and end with '>' next to the address.
+fn branch_gutter(arrows: &[BranchArrow], address: u16, label_row: bool) -> String {
+    let mut chars = [' '; BRANCH_LANES + 1];
+
+    for arrow in arrows {
+        let column = BRANCH_LANES - 1 - arrow.lane;
+        let (top, bottom) = (arrow.top(), arrow.bottom());
+
+        if top == bottom {
+            // Jump to itself
+            if address == top && !label_row {
+                chars[BRANCH_LANES] = '↺';
+            }
+        } else if label_row || (top < address && address < bottom) {
+            if top < address && address <= bottom {
+                chars[column] = match chars[column] {
+                    '─' => '┼',
+                    _ => '│',
+                };
+            }
+        } else if address == top || address == bottom {
+            chars[column] = if address == top { '┌' } else { '└' };
+            for ch in chars.iter_mut().take(BRANCH_LANES).skip(column + 1) {
+                *ch = match *ch {
+                    '│' | '┼' => '┼',
+                    _ => '─',
+                };
+            }
+            if chars[BRANCH_LANES] != '>' {
+                chars[BRANCH_LANES] = if address == arrow.target { '>' } else { '─' };
+            }
+        }
+    }
+
+    chars.iter().collect()
 }
 
 pub struct PmemTableState {
@@ -15,6 +105,8 @@ pub struct PmemTableState {
     pub viewport_begin: u32,
     pub focus_executing: bool, // True if table should center around executing instruction
     pub is_focussed: bool,
+    pub show_source: bool, // Show the assembly source instead, if there is one
+    pub source_viewport_begin: usize,
 }
 
 impl Default for PmemTableState {
@@ -24,6 +116,8 @@ impl Default for PmemTableState {
             viewport_begin: 0,
             focus_executing: true,
             is_focussed: false,
+            show_source: true,
+            source_viewport_begin: 0,
         }
     }
 }
@@ -35,11 +129,30 @@ impl PmemTableState {
     }
 }
 
+// Mark breakpoints, tracepoints and conditional ones in different colors
+pub fn breakpoint_color(breakpoints: &BreakpointManager, address: u16) -> Color {
+    match breakpoints.get(address) {
+        Some(bp) if !bp.enabled => Color::DarkGray,
+        Some(bp) if bp.is_tracepoint() => Color::Cyan,
+        Some(bp) if bp.is_conditional() => Color::Yellow,
+        Some(_) => Color::Red,
+        None => Color::LightMagenta,
+    }
+}
+
 impl<'a> PmemTableWidget<'a> {
-    pub fn new(cpu: &'a CpuState, program: &'a Program) -> Self {
+    pub fn new(
+        cpu: &'a CpuState,
+        program: &'a Program,
+        breakpoints: &'a BreakpointManager,
+        annotations: &'a Annotations,
+    ) -> Self {
         PmemTableWidget {
             program_counter: &cpu.pcounter,
+            cpu,
             program,
+            breakpoints,
+            annotations,
         }
     }
 }
@@ -110,8 +223,21 @@ impl<'a> StatefulWidget for PmemTableWidget<'a> {
             viewport_end = state.viewport_begin + visible_lines;
         }
 
+        // Jumps crossing the viewport, drawn as arrows in the gutter
+        let viewport_range = state.viewport_begin..viewport_end;
+        let jumps = (0..self.program.operations.len() as u16)
+            .filter_map(|source| {
+                let target = self.program.jump_target(source, self.cpu)?;
+                let (top, bottom) = (source.min(target) as u32, source.max(target) as u32);
+                (top < viewport_range.end && viewport_range.start <= bottom)
+                    .then_some((source, target))
+            })
+            .collect();
+        let arrows = assign_lanes(jumps);
+
         // Create the empty row vector to be populated
         let mut rows = Vec::new();
+        let mut focus_row = 0; // Index of the selected instruction's row
 
         // Build the rows, make sure to clamp the viewport to progam begin/end
         for i in state
@@ -119,17 +245,22 @@ impl<'a> StatefulWidget for PmemTableWidget<'a> {
             .clamp(0, self.program.operations.len() as u32)
             ..viewport_end.clamp(0, self.program.operations.len() as u32)
         {
+            // Labels get a row of their own above the instruction
+            for label in self.program.symbols.labels_at(i as u16) {
+                rows.push(Row::new(vec![
+                    Cell::from(branch_gutter(&arrows, i as u16, true)).fg(Color::Blue),
+                    Cell::from(""),
+                    Cell::from(""),
+                    Cell::from(format!("{}:", label)).fg(Color::Yellow),
+                ]));
+            }
+
             let mut cells = Vec::new();
 
-            // Build the cells, address first
+            // Build the cells, branch arrows and address first
+            cells.push(Cell::from(branch_gutter(&arrows, i as u16, false)).fg(Color::Blue));
             let address_str = format!("{:04x}", i);
-            cells.push(
-                Cell::from(address_str).fg(match self.program.breakpoints[i as usize] {
-                    // Mark breakpoints
-                    true => Color::Red,
-                    false => Color::LightMagenta,
-                }),
-            );
+            cells.push(Cell::from(address_str).fg(breakpoint_color(self.breakpoints, i as u16)));
             // Now the instruction hex code
             cells.push(
                 Cell::from(format!(
@@ -138,10 +269,19 @@ impl<'a> StatefulWidget for PmemTableWidget<'a> {
                 ))
                 .fg(Color::Green),
             );
-            // Finally the disassembled representation
-            cells.push(Cell::from(
-                self.program.operations[i as usize].get_assembly_string(),
-            ));
+            // The disassembled representation
+            cells.push(Cell::from(self.program.assembly_string(i as u16, self.cpu)));
+            // Finally bookmark and comment
+            let mut spans = Vec::new();
+            if let Some(annotation) = self.annotations.get(i as u16) {
+                if let Some(name) = &annotation.bookmark {
+                    spans.push(Span::from(format!("[{}] ", name)).light_cyan());
+                }
+                if let Some(comment) = &annotation.comment {
+                    spans.push(Span::from(format!("; {}", comment)).dark_gray());
+                }
+            }
+            cells.push(Cell::from(Line::from(spans)));
 
             let mut row = Row::new(cells);
 
@@ -152,12 +292,21 @@ impl<'a> StatefulWidget for PmemTableWidget<'a> {
             }
             if i == state.selected {
                 row = row.bold();
+                focus_row = rows.len();
             }
 
             // Push the row
             rows.push(row);
         }
 
+        // Label rows push instructions out of the viewport, drop rows from the top
+        // so the selected instruction stays visible
+        let excess = rows.len().saturating_sub(visible_lines as usize);
+        let overflow =
+            (focus_row + 1 + scroll_offset as usize).saturating_sub(visible_lines as usize);
+        rows.drain(..excess.min(overflow));
+        rows.truncate(visible_lines as usize);
+
         let table = Table::new(rows)
             .block(
                 Block::default()
@@ -172,9 +321,11 @@ impl<'a> StatefulWidget for PmemTableWidget<'a> {
             .column_spacing(1)
             .widths(
                 [
+                    Constraint::Length(BRANCH_LANES as u16 + 1),
                     Constraint::Min(5),
                     Constraint::Min(6),
-                    Constraint::Percentage(80),
+                    Constraint::Percentage(45),
+                    Constraint::Percentage(35),
                 ]
                 .as_ref(),
             );
@@ -182,3 +333,46 @@ impl<'a> StatefulWidget for PmemTableWidget<'a> {
         Widget::render(table, area, buf)
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn branch_gutter_test() {
+        // A loop from 0xa back to 0x3 containing a forward jump from 0x6 to 0xb,
+        // and a jump to itself at 0xc
+        let arrows = assign_lanes(vec![(0xa, 0x3), (0x6, 0xb), (0xc, 0xc)]);
+        let lane_of = |source| arrows.iter().find(|a| a.source == source).unwrap().lane;
+        assert_eq!(lane_of(0x6), 0);
+        assert_eq!(lane_of(0xa), 1);
+
+        let gutter: Vec<String> = (0x2..=0xc)
+            .map(|address| branch_gutter(&arrows, address, false))
+            .collect();
+        assert_eq!(
+            gutter,
+            [
+                "    ",
+                " ┌─>",
+                " │  ",
+                " │  ",
+                " │┌─",
+                " ││ ",
+                " ││ ",
+                " ││ ",
+                " └┼─",
+                "  └>",
+                "   ↺",
+            ]
+        );
+        assert_eq!(branch_gutter(&arrows, 0x3, true), "    ");
+        assert_eq!(branch_gutter(&arrows, 0x4, true), " │  ");
+        assert_eq!(branch_gutter(&arrows, 0xb, true), "  │ ");
+
+        // No free lane left for the outermost jump
+        let arrows = assign_lanes(vec![(0x0, 0x10), (0x1, 0x9), (0x2, 0x8), (0x3, 0x7)]);
+        assert_eq!(arrows.len(), BRANCH_LANES);
+        assert!(arrows.iter().all(|arrow| arrow.source != 0x0));
+    }
+}
@@ -1,19 +1,222 @@
-use ratatui::prelude::{Alignment};
-use ratatui::style::{Style, Stylize};
-use ratatui::text::Line;
-use ratatui::widgets::{Block, BorderType, Borders, Paragraph, Widget, Wrap};
+use std::fmt::Display;
+
+use num_traits::{Bounded, Num, Unsigned};
+use ratatui::prelude::{Alignment, Buffer, Rect};
+use ratatui::style::{Color, Style, Stylize};
+use ratatui::text::{Line, Span};
+use ratatui::widgets::{Block, BorderType, Borders, Paragraph, StatefulWidget, Widget, Wrap};
+
+use crate::backend::expression::parse_number;
+
+// Number of entries remembered per prompt
+const MAX_HISTORY: usize = 20;
 
 pub struct PromptWidget<'a> {
-    input_buffer: &'a str,
     prompt_text: &'a str,
 }
 
-impl<'a> Widget for PromptWidget<'a> {
-    fn render(self, area: ratatui::prelude::Rect, buf: &mut ratatui::prelude::Buffer) {
-        let text = vec![
+// Values that can be entered in a prompt. Parsing errors are shown below the input.
+pub trait PromptValue: Sized {
+    fn parse_prompt(text: &str) -> Result<Self, String>;
+}
+
+impl PromptValue for String {
+    fn parse_prompt(text: &str) -> Result<Self, String> {
+        Ok(String::from(text))
+    }
+}
+
+impl PromptValue for bool {
+    fn parse_prompt(text: &str) -> Result<Self, String> {
+        match text.trim().to_lowercase().as_str() {
+            "true" | "yes" | "y" => Ok(true),
+            "false" | "no" | "n" => Ok(false),
+            _ => Err(format!("Expected true or false, not '{}'", text.trim())),
+        }
+    }
+}
+
+// The number syntax of the console, with more helpful errors than
+// expression::parse_number for what went wrong
+fn parse_unsigned<T: Num + Unsigned + Bounded + Display>(text: &str) -> Result<T, String> {
+    let text = text.trim();
+    let lowercase = text.to_lowercase();
+    let kind = match lowercase.get(..2) {
+        Some("0x") => "hex",
+        Some("0b") => "binary",
+        _ => "decimal",
+    };
+
+    parse_number::<T>(text).map_err(|_| match text {
+        "" => String::from("Enter a number, e.g. 42, 0x1F or 0b101"),
+        _ if text.starts_with('-') => String::from("Negative numbers are not allowed"),
+        _ if lowercase == "0x" || lowercase == "0b" => format!("Missing digits after '{}'", text),
+        _ => match parse_number::<u128>(text) {
+            Ok(value) => format!("{} is too large, the maximum is {}", value, T::max_value()),
+            Err(_) => format!("'{}' is not a valid {} number", text, kind),
+        },
+    })
+}
+
+macro_rules! impl_prompt_value_unsigned {
+    ($($t:ty),*) => {
+        $(impl PromptValue for $t {
+            fn parse_prompt(text: &str) -> Result<Self, String> {
+                parse_unsigned(text)
+            }
+        })*
+    };
+}
+
+impl_prompt_value_unsigned!(u16, u32, u64);
+
+// Line editor behind the prompt
+#[derive(Default)]
+pub struct PromptState {
+    pub input: String,
+    pub error: Option<String>, // Why the last input was rejected
+
+    cursor: usize, // Position in characters
+    history: Vec<String>,
+    history_index: Option<usize>, // Entry shown while browsing the history
+}
+
+impl PromptState {
+    pub fn new(history: Vec<String>) -> Self {
+        PromptState {
+            history,
+            ..Default::default()
+        }
+    }
+
+    pub fn into_history(self) -> Vec<String> {
+        self.history
+    }
+
+    fn byte_index(&self, cursor: usize) -> usize {
+        self.input
+            .char_indices()
+            .nth(cursor)
+            .map_or(self.input.len(), |(index, _)| index)
+    }
+
+    fn set_input(&mut self, input: String) {
+        self.cursor = input.chars().count();
+        self.input = input;
+    }
+
+    pub fn insert(&mut self, ch: char) {
+        let index = self.byte_index(self.cursor);
+        self.input.insert(index, ch);
+        self.cursor += 1;
+        self.error = None;
+    }
+
+    pub fn backspace(&mut self) {
+        if self.cursor > 0 {
+            self.cursor -= 1;
+            self.delete();
+        }
+    }
+
+    pub fn delete(&mut self) {
+        if self.cursor < self.input.chars().count() {
+            let index = self.byte_index(self.cursor);
+            self.input.remove(index);
+            self.error = None;
+        }
+    }
+
+    pub fn move_cursor(&mut self, offset: i32) {
+        let len = self.input.chars().count() as i32;
+        self.cursor = (self.cursor as i32 + offset).clamp(0, len) as usize;
+    }
+
+    pub fn home(&mut self) {
+        self.cursor = 0;
+    }
+
+    pub fn end(&mut self) {
+        self.cursor = self.input.chars().count();
+    }
+
+    pub fn history_previous(&mut self) {
+        let index = match self.history_index {
+            Some(index) => index.saturating_sub(1),
+            None if self.history.is_empty() => return,
+            None => self.history.len() - 1,
+        };
+
+        self.history_index = Some(index);
+        self.set_input(self.history[index].clone());
+    }
+
+    pub fn history_next(&mut self) {
+        match self.history_index {
+            Some(index) if index + 1 < self.history.len() => {
+                self.history_index = Some(index + 1);
+                self.set_input(self.history[index + 1].clone());
+            }
+            Some(_) => {
+                self.history_index = None;
+                self.set_input(String::new());
+            }
+            None => {}
+        }
+    }
+
+    // Parse the input, on success it is added to the history. On failure the
+    // input is kept for correcting it and the error is shown.
+    pub fn submit<T: PromptValue>(&mut self) -> Option<T> {
+        self.submit_with(T::parse_prompt)
+    }
+
+    // Like submit, with a parser that may depend on more than the text,
+    // e.g. expressions evaluated against the CPU state
+    pub fn submit_with<T>(&mut self, parse: impl FnOnce(&str) -> Result<T, String>) -> Option<T> {
+        match parse(&self.input) {
+            Ok(value) => {
+                let line = self.input.clone();
+                if !line.trim().is_empty() && self.history.last() != Some(&line) {
+                    self.history.push(line);
+                    if self.history.len() > MAX_HISTORY {
+                        self.history.remove(0);
+                    }
+                }
+                Some(value)
+            }
+            Err(msg) => {
+                self.error = Some(msg);
+                None
+            }
+        }
+    }
+}
+
+impl<'a> StatefulWidget for PromptWidget<'a> {
+    type State = PromptState;
+
+    fn render(self, area: Rect, buf: &mut Buffer, state: &mut PromptState) {
+        // Show the cursor as a reversed character
+        let index = state.byte_index(state.cursor);
+        let (before, rest) = state.input.split_at(index);
+        let mut rest_chars = rest.chars();
+        let cursor = rest_chars.next().map_or(String::from(" "), String::from);
+
+        let mut text = vec![
             Line::styled(self.prompt_text, Style::default().light_blue()),
-            Line::styled(self.input_buffer, Style::default()),
+            Line::from(vec![
+                Span::raw(before),
+                Span::styled(cursor, Style::default().reversed()),
+                Span::raw(rest_chars.as_str()),
+            ]),
         ];
+        if let Some(error) = &state.error {
+            text.push(Line::styled(
+                error.as_str(),
+                Style::default().fg(Color::Red),
+            ));
+        }
 
         let paragraph = Paragraph::new(text).wrap(Wrap { trim: false }).block(
             Block::default()
@@ -28,10 +231,63 @@ impl<'a> Widget for PromptWidget<'a> {
 }
 
 impl<'a> PromptWidget<'a> {
-    pub fn new(prompt_text: &'a str, input_buffer: &'a str) -> Self {
-        PromptWidget {
-            input_buffer,
-            prompt_text,
+    pub fn new(prompt_text: &'a str) -> Self {
+        PromptWidget { prompt_text }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parse_test() {
+        assert_eq!(u32::parse_prompt("0x1F"), Ok(0x1f));
+        assert_eq!(u32::parse_prompt(" 0b101 "), Ok(5));
+        assert_eq!(u32::parse_prompt("250"), Ok(250));
+        assert_eq!(
+            u16::parse_prompt("70000"),
+            Err(String::from("70000 is too large, the maximum is 65535"))
+        );
+        assert_eq!(
+            u32::parse_prompt("0x1G"),
+            Err(String::from("'0x1G' is not a valid hex number"))
+        );
+        assert!(u32::parse_prompt("0x").is_err());
+        assert!(u32::parse_prompt("-1").is_err());
+        assert_eq!(bool::parse_prompt("Yes"), Ok(true));
+        assert!(bool::parse_prompt("maybe").is_err());
+    }
+
+    #[test]
+    fn editing_test() {
+        let mut state = PromptState::new(vec![String::from("0x10")]);
+        for ch in "0x1".chars() {
+            state.insert(ch);
         }
+        state.home();
+        state.delete();
+        state.move_cursor(1);
+        state.insert('b');
+        assert_eq!(state.input, "xb1");
+
+        // Rejected input stays for correction
+        assert_eq!(state.submit::<u32>(), None);
+        assert!(state.error.is_some());
+        state.end();
+        state.backspace();
+        state.backspace();
+        state.backspace();
+        state.insert('7');
+        assert_eq!(state.error, None);
+        assert_eq!(state.submit::<u32>(), Some(7));
+
+        state.history_previous();
+        assert_eq!(state.input, "7");
+        state.history_previous();
+        assert_eq!(state.input, "0x10");
+        state.history_next();
+        state.history_next();
+        assert_eq!(state.input, "");
     }
 }
@@ -1,45 +1,89 @@
+use crate::backend::changes::ChangeTracker;
 use crate::backend::runtime::{CpuState, RAM_SIZE};
+use crate::backend::watchpoints::WatchpointManager;
+
+use super::number_format::{ascii_char, NumberFormat};
+use super::registers::change_style;
 
 use ratatui::prelude::Constraint;
 use ratatui::prelude::{Alignment, Buffer, Color, Rect};
-use ratatui::style::Stylize;
+use ratatui::style::{Style, Stylize};
 use ratatui::widgets::{
     Block, BorderType, Borders, Cell, Padding, Row, StatefulWidget, Table, Widget,
 };
 
-const RAM_MAX_LINES: u32 = (RAM_SIZE / 4) as u32;
-
 pub struct RamTableWidget<'a> {
     ram_ref: &'a [u16; RAM_SIZE],
+    cpu: &'a CpuState,
+    watchpoints: &'a WatchpointManager,
+    changes: &'a ChangeTracker,
 }
 
 pub struct RamTableState {
     pub starting_row: u32,
     pub is_focussed: bool,
+    pub cursor: u16,         // Selected address
+    pub follow_writes: bool, // Move the cursor to the address written by the last step
+    format: NumberFormat,
+
+    // Hex digits typed so far while editing the word under the cursor
+    pub edit_buffer: Option<String>,
 }
 
 impl<'a> RamTableWidget<'a> {
-    pub fn new(cpu: &'a CpuState) -> Self {
-        RamTableWidget { ram_ref: &cpu.ram }
+    pub fn new(
+        cpu: &'a CpuState,
+        watchpoints: &'a WatchpointManager,
+        changes: &'a ChangeTracker,
+    ) -> Self {
+        RamTableWidget {
+            ram_ref: &cpu.ram,
+            cpu,
+            watchpoints,
+            changes,
+        }
     }
 }
 
 impl<'a> RamTableWidget<'a> {
-    fn construct_row(&self, memory_row_index: u32) -> Row {
-        let row_mem_address = memory_row_index * 4;
+    fn construct_row(&self, memory_row_index: u32, state: &RamTableState) -> Row<'_> {
+        let row_mem_address = memory_row_index * state.words_per_row();
+        let row_end = row_mem_address + state.words_per_row();
 
         // Push row address
         let mut cells =
             vec![Cell::from(format!("{:04X}", row_mem_address)).fg(Color::LightMagenta)];
 
         // Push the row values
-        for address in row_mem_address..(row_mem_address + 4) {
-            cells.push(Cell::from(format!(
-                "{:04X}",
-                self.ram_ref[address as usize]
-            )));
+        for address in row_mem_address..row_end {
+            let is_cursor = state.is_focussed && address == state.cursor as u32;
+
+            let mut cell = match (&state.edit_buffer, is_cursor) {
+                (Some(digits), true) => Cell::from(format!("{:_<4}", digits)).fg(Color::LightBlue),
+                _ => Cell::from(state.format.format(self.ram_ref[address as usize])),
+            };
+
+            // Mark watched addresses, recent writes take precedence
+            if self.watchpoints.is_watched(address as u16) {
+                cell = cell.fg(Color::Yellow);
+            }
+            let change = change_style(self.changes.ram_age(address as u16, self.cpu));
+            if change != Style::default() {
+                cell = cell.style(change);
+            }
+            if is_cursor {
+                cell = cell.reversed();
+            }
+
+            cells.push(cell);
         }
 
+        // The row as text, like in a hex dump
+        let text: String = (row_mem_address..row_end)
+            .map(|address| ascii_char(self.ram_ref[address as usize]))
+            .collect();
+        cells.push(Cell::from(text).fg(Color::DarkGray));
+
         Row::new(cells)
     }
 }
@@ -50,22 +94,29 @@ impl<'a> StatefulWidget for RamTableWidget<'a> {
     fn render(self, area: Rect, buf: &mut Buffer, state: &mut Self::State) {
         // Check how many lines we can render
         let n_rows = (area.height - 2) as u32; // -2 because of borders
+        state.scroll_to_cursor(n_rows);
         let end_row = state.starting_row + n_rows;
 
-        // Build a list of indices... remember, we need to wrap around LINE_MAX
+        // Build a list of indices... remember, we need to wrap around the last row
         let indices: Vec<u32> = (state.starting_row..end_row)
-            .map(|i| i % RAM_MAX_LINES)
+            .map(|i| i % state.max_rows())
             .collect();
         // Construct the rows
         let rows = indices
             .iter()
-            .map(|row_index| self.construct_row(*row_index));
+            .map(|row_index| self.construct_row(*row_index, state));
+
+        // Address, the words and their characters
+        let value_width = state.format.width().max(4) + 1;
+        let mut widths = vec![Constraint::Length(6)];
+        widths.extend((0..state.words_per_row()).map(|_| Constraint::Length(value_width)));
+        widths.push(Constraint::Length(state.words_per_row() as u16));
 
         // Construct the table
         let table = Table::new(rows)
             .block(
                 Block::default()
-                    .title(" RAM ")
+                    .title(format!(" RAM ({}) ", state.format.name()))
                     .title_alignment(Alignment::Center)
                     .borders(Borders::ALL)
                     .border_type(match state.is_focussed {
@@ -75,16 +126,7 @@ impl<'a> StatefulWidget for RamTableWidget<'a> {
                     .padding(Padding::new(1, 1, 0, 0)),
             )
             .column_spacing(0)
-            .widths(
-                [
-                    Constraint::Percentage(20),
-                    Constraint::Percentage(20),
-                    Constraint::Percentage(20),
-                    Constraint::Percentage(20),
-                    Constraint::Percentage(20),
-                ]
-                .as_ref(),
-            );
+            .widths(&widths);
 
         Widget::render(table, area, buf)
     }
@@ -95,22 +137,87 @@ impl Default for RamTableState {
         RamTableState {
             starting_row: 0,
             is_focussed: false,
+            cursor: 0,
+            follow_writes: false,
+            format: NumberFormat::Hex,
+            edit_buffer: None,
         }
     }
 }
 
 impl RamTableState {
+    // Binary words are too wide to fit 4 of them into a row
+    pub fn words_per_row(&self) -> u32 {
+        match self.format {
+            NumberFormat::Binary => 2,
+            _ => 4,
+        }
+    }
+
+    fn max_rows(&self) -> u32 {
+        RAM_SIZE as u32 / self.words_per_row()
+    }
+
+    // Switch to the next format, keeping the cursor at the top of the view
+    pub fn cycle_format(&mut self) {
+        self.format = self.format.next();
+        self.starting_row = self.cursor as u32 / self.words_per_row();
+    }
+
     pub fn scroll(&mut self, offset: i32) {
         let tmp = self.starting_row as i32 + offset;
 
         if tmp < 0 {
-            self.starting_row = ((RAM_MAX_LINES as i32) + tmp) as u32;
+            self.starting_row = ((self.max_rows() as i32) + tmp) as u32;
         } else {
-            self.starting_row = (tmp as u32) % RAM_MAX_LINES;
+            self.starting_row = (tmp as u32) % self.max_rows();
         }
     }
 
     pub fn goto_address(&mut self, address: u32) {
-        self.starting_row = address.clamp(0, RAM_SIZE as u32) / 4;
+        self.cursor = address.clamp(0, RAM_SIZE as u32 - 1) as u16;
+        self.starting_row = self.cursor as u32 / self.words_per_row();
+    }
+
+    // Move the cursor by the given number of rows
+    pub fn move_rows(&mut self, rows: i32) {
+        self.move_cursor(rows * self.words_per_row() as i32);
+    }
+
+    // Move the cursor by the given number of words, wrapping around at the ends of RAM
+    pub fn move_cursor(&mut self, offset: i32) {
+        self.cursor = (self.cursor as i32 + offset).rem_euclid(RAM_SIZE as i32) as u16;
+    }
+
+    // Adjust the visible rows so the cursor stays in view
+    fn scroll_to_cursor(&mut self, n_rows: u32) {
+        let max_rows = self.max_rows();
+        let cursor_row = self.cursor as u32 / self.words_per_row();
+        let distance = (cursor_row + max_rows - self.starting_row) % max_rows;
+
+        if distance >= n_rows {
+            self.starting_row = match cursor_row < self.starting_row {
+                true => cursor_row,
+                false => (cursor_row + max_rows + 1 - n_rows.max(1)) % max_rows,
+            };
+        }
+    }
+
+    pub fn is_editing(&self) -> bool {
+        self.edit_buffer.is_some()
+    }
+
+    // Add a hex digit to the edit buffer, starting the edit if necessary
+    pub fn push_edit_digit(&mut self, digit: char) {
+        let buffer = self.edit_buffer.get_or_insert_with(String::new);
+        if buffer.len() < 4 && digit.is_ascii_hexdigit() {
+            buffer.push(digit.to_ascii_uppercase());
+        }
+    }
+
+    // Finish editing, returns the entered value if any digits were typed
+    pub fn take_edit_value(&mut self) -> Option<u16> {
+        let digits = self.edit_buffer.take()?;
+        u16::from_str_radix(&digits, 16).ok()
     }
 }
@@ -1,32 +1,163 @@
 use ratatui::prelude::{Alignment, Constraint};
 
-use ratatui::style::Stylize;
-use ratatui::widgets::{Block, Borders, Cell, Row, StatefulWidget, Table, Widget};
+use ratatui::style::{Color, Style, Stylize};
+use ratatui::widgets::{Block, BorderType, Borders, Cell, Row, StatefulWidget, Table, Widget};
 
+use crate::backend::changes::ChangeTracker;
+use crate::backend::expression::{self, Flag};
 use crate::backend::runtime::{CpuState, Flags};
+use crate::backend::symbols::SymbolTable;
+
+use super::number_format::NumberFormat;
 
 pub struct RegistersDisplayWidget<'a> {
     pcounter_ref: &'a u16,
     registers_ref: &'a [u16; 8],
     flags_ref: &'a Flags,
+    cpu: &'a CpuState,
+    changes: &'a ChangeTracker,
+}
+
+// Values changed by the last step are highlighted, the highlight fades over
+// the following steps
+pub fn change_style(age: Option<u64>) -> Style {
+    match age {
+        Some(0) => Style::default().fg(Color::LightRed).bold(),
+        Some(1) => Style::default().fg(Color::LightRed),
+        Some(2..=3) => Style::default().fg(Color::Red),
+        _ => Style::default(),
+    }
+}
+
+// Everything that can be selected and edited in the registers context
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+pub enum RegisterField {
+    Register(usize),
+    ProgramCounter,
+    Zero,
+    Carry,
+    Overflow,
+}
+
+impl RegisterField {
+    pub fn name(&self) -> String {
+        match self {
+            RegisterField::Register(i) => format!("reg{}", i),
+            RegisterField::ProgramCounter => String::from("pc"),
+            RegisterField::Zero => String::from("zero"),
+            RegisterField::Carry => String::from("carry"),
+            RegisterField::Overflow => String::from("overflow"),
+        }
+    }
+
+    pub fn is_flag(&self) -> bool {
+        matches!(
+            self,
+            RegisterField::Zero | RegisterField::Carry | RegisterField::Overflow
+        )
+    }
+
+    pub fn get(&self, cpu: &CpuState) -> u16 {
+        match self {
+            RegisterField::Register(i) => cpu.registers[*i],
+            RegisterField::ProgramCounter => cpu.pcounter,
+            RegisterField::Zero => cpu.flags.zero as u16,
+            RegisterField::Carry => cpu.flags.carry as u16,
+            RegisterField::Overflow => cpu.flags.overflow as u16,
+        }
+    }
+
+    // Parse user input: an expression (see backend::expression), a character in
+    // single quotes or, for flags, true/false
+    pub fn parse_value(
+        &self,
+        text: &str,
+        cpu: &CpuState,
+        symbols: &SymbolTable,
+    ) -> Result<u16, String> {
+        let text = text.trim();
+        match text {
+            "true" if self.is_flag() => return Ok(1),
+            "false" if self.is_flag() => return Ok(0),
+            _ => {}
+        }
+
+        let quoted = text.strip_prefix('\'').and_then(|t| t.strip_suffix('\''));
+        if let Some(inner) = quoted {
+            let mut chars = inner.chars();
+            return match (chars.next(), chars.next()) {
+                (Some(ch), None) if (ch as u32) <= u16::MAX as u32 => Ok(ch as u16),
+                _ => Err(format!("Invalid character literal {}", text)),
+            };
+        }
+
+        expression::evaluate(text, cpu, symbols)
+    }
+
+    // Flags are set for any non-zero value
+    pub fn set(&self, cpu: &mut CpuState, value: u16) {
+        match self {
+            RegisterField::Register(i) => cpu.registers[*i] = value,
+            RegisterField::ProgramCounter => cpu.pcounter = value,
+            RegisterField::Zero => cpu.flags.zero = value != 0,
+            RegisterField::Carry => cpu.flags.carry = value != 0,
+            RegisterField::Overflow => cpu.flags.overflow = value != 0,
+        }
+    }
 }
 
 pub struct RegistersDisplayState {
-    pub use_hex: bool,
+    pub format: NumberFormat,
+    pub is_focussed: bool,
+
+    // Selected cell, the table is laid out as 6 rows of 2 fields
+    selected_row: usize,
+    selected_column: usize,
 }
 
 impl Default for RegistersDisplayState {
     fn default() -> Self {
-        RegistersDisplayState { use_hex: true }
+        RegistersDisplayState {
+            format: NumberFormat::Hex,
+            is_focussed: false,
+            selected_row: 2,
+            selected_column: 0,
+        }
     }
 }
 
 impl RegistersDisplayState {
     fn get_number_repr(&self, num: u16) -> String {
-        if self.use_hex {
-            format!("{:#X}", num)
-        } else {
-            format!("{}", num)
+        match self.format {
+            NumberFormat::Hex => format!("{:#X}", num),
+            format => format.format(num),
+        }
+    }
+
+    fn field_at(row: usize, column: usize) -> RegisterField {
+        match (row, column) {
+            (0, 0) => RegisterField::Zero,
+            (0, _) => RegisterField::ProgramCounter,
+            (1, 0) => RegisterField::Carry,
+            (1, _) => RegisterField::Overflow,
+            (row, column) => RegisterField::Register(row - 2 + 4 * column),
+        }
+    }
+
+    pub fn selected(&self) -> RegisterField {
+        Self::field_at(self.selected_row, self.selected_column)
+    }
+
+    pub fn move_selection(&mut self, rows: i32, columns: i32) {
+        self.selected_row = (self.selected_row as i32 + rows).clamp(0, 5) as usize;
+        self.selected_column = (self.selected_column as i32 + columns).clamp(0, 1) as usize;
+    }
+
+    fn cell<'b>(&self, field: RegisterField, text: String, age: Option<u64>) -> Cell<'b> {
+        let cell = Cell::from(text).style(change_style(age));
+        match self.is_focussed && field == self.selected() {
+            true => cell.reversed(),
+            false => cell,
         }
     }
 }
@@ -41,29 +172,43 @@ impl<'a> StatefulWidget for RegistersDisplayWidget<'a> {
         state: &mut Self::State,
     ) {
         let mut rows = Vec::new();
+        let cell = |field: RegisterField, text: String| state.cell(field, text, self.age(field));
 
-        // Rows containing the zero and carry flag and PC
+        // Rows containing the flags and PC
         rows.push(Row::new(vec![
             Cell::from("zero").green(),
-            Cell::from(format!("{}", self.flags_ref.zero)),
+            cell(RegisterField::Zero, format!("{}", self.flags_ref.zero)),
             Cell::from("pc").magenta(),
-            Cell::from(state.get_number_repr(*self.pcounter_ref)),
+            cell(
+                RegisterField::ProgramCounter,
+                state.get_number_repr(*self.pcounter_ref),
+            ),
         ]));
 
         rows.push(Row::new(vec![
             Cell::from("carry").green(),
-            Cell::from(format!("{}", self.flags_ref.carry)),
+            cell(RegisterField::Carry, format!("{}", self.flags_ref.carry)),
+            Cell::from("overflow").green(),
+            cell(
+                RegisterField::Overflow,
+                format!("{}", self.flags_ref.overflow),
+            ),
         ]));
 
         // Build the 4 register rows
         let mut register_rows = (0..4)
-            .into_iter()
             .map(|i| {
                 Row::new(vec![
                     Cell::from(format!("reg{}", i)).blue(),
-                    Cell::from(state.get_number_repr(self.registers_ref[i])),
+                    cell(
+                        RegisterField::Register(i),
+                        state.get_number_repr(self.registers_ref[i]),
+                    ),
                     Cell::from(format!("reg{}", i + 4)).blue(),
-                    Cell::from(state.get_number_repr(self.registers_ref[i + 4])),
+                    cell(
+                        RegisterField::Register(i + 4),
+                        state.get_number_repr(self.registers_ref[i + 4]),
+                    ),
                 ])
             })
             .collect();
@@ -74,9 +219,13 @@ impl<'a> StatefulWidget for RegistersDisplayWidget<'a> {
             .column_spacing(1)
             .block(
                 Block::default()
-                    .title(" Registers / Flags")
+                    .title(format!(" Registers / Flags ({}) ", state.format.name()))
                     .title_alignment(Alignment::Center)
-                    .borders(Borders::ALL),
+                    .borders(Borders::ALL)
+                    .border_type(match state.is_focussed {
+                        true => BorderType::Thick,
+                        false => BorderType::Plain,
+                    }),
             )
             .widths(
                 [
@@ -93,11 +242,58 @@ impl<'a> StatefulWidget for RegistersDisplayWidget<'a> {
 }
 
 impl<'a> RegistersDisplayWidget<'a> {
-    pub fn new(cpu: &'a CpuState) -> Self {
+    pub fn new(cpu: &'a CpuState, changes: &'a ChangeTracker) -> Self {
         RegistersDisplayWidget {
             pcounter_ref: &cpu.pcounter,
             registers_ref: &cpu.registers,
             flags_ref: &cpu.flags,
+            cpu,
+            changes,
+        }
+    }
+
+    // Steps since the program changed the field, the program counter changes
+    // with every step and is not highlighted
+    fn age(&self, field: RegisterField) -> Option<u64> {
+        match field {
+            RegisterField::Register(i) => self.changes.register_age(i, self.cpu),
+            RegisterField::ProgramCounter => None,
+            RegisterField::Zero => self.changes.flag_age(Flag::Zero, self.cpu),
+            RegisterField::Carry => self.changes.flag_age(Flag::Carry, self.cpu),
+            RegisterField::Overflow => self.changes.flag_age(Flag::Overflow, self.cpu),
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn field_test() {
+        let mut state = RegistersDisplayState::default();
+        assert_eq!(state.selected(), RegisterField::Register(0));
+        state.move_selection(3, 1);
+        assert_eq!(state.selected(), RegisterField::Register(7));
+        state.move_selection(-10, 0);
+        assert_eq!(state.selected(), RegisterField::ProgramCounter);
+
+        let mut cpu = CpuState::default();
+        let symbols = SymbolTable::default();
+        let reg = RegisterField::Register(3);
+        assert_eq!(reg.parse_value("'a'", &cpu, &symbols), Ok(0x61));
+        assert_eq!(reg.parse_value("0x1f", &cpu, &symbols), Ok(0x1f));
+        assert!(reg.parse_value("true", &cpu, &symbols).is_err());
+        assert!(reg.parse_value("'ab'", &cpu, &symbols).is_err());
+        reg.set(&mut cpu, 5);
+        assert_eq!(cpu.registers[3], 5);
+        assert_eq!(reg.parse_value("reg3 * 2 + 1", &cpu, &symbols), Ok(11));
+
+        assert_eq!(
+            RegisterField::Carry.parse_value("true", &cpu, &symbols),
+            Ok(1)
+        );
+        RegisterField::Carry.set(&mut cpu, 2);
+        assert!(cpu.flags.carry);
+    }
+}
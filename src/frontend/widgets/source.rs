@@ -0,0 +1,126 @@
+use crate::backend::breakpoints::BreakpointManager;
+use crate::backend::runtime::CpuState;
+use crate::backend::source_map::SourceMap;
+
+use ratatui::prelude::{Alignment, Buffer, Color, Constraint, Rect};
+use ratatui::style::Stylize;
+use ratatui::text::{Line, Span};
+use ratatui::widgets::{Block, BorderType, Borders, Cell, Row, StatefulWidget, Table, Widget};
+
+use super::pmem::{breakpoint_color, PmemTableState};
+
+// Assembly source of the program, shares its selection with the program memory view
+pub struct SourceWidget<'a> {
+    source: &'a SourceMap,
+    program_counter: &'a u16,
+    breakpoints: &'a BreakpointManager,
+}
+
+impl<'a> SourceWidget<'a> {
+    pub fn new(
+        cpu: &'a CpuState,
+        source: &'a SourceMap,
+        breakpoints: &'a BreakpointManager,
+    ) -> Self {
+        SourceWidget {
+            source,
+            program_counter: &cpu.pcounter,
+            breakpoints,
+        }
+    }
+}
+
+// Split a line into code and comment, the comment is shown in a different color
+fn highlight_line(text: &str) -> Line<'static> {
+    match text.find('#') {
+        Some(index) => Line::from(vec![
+            Span::raw(String::from(&text[..index])),
+            Span::raw(String::from(&text[index..])).gray().italic(),
+        ]),
+        None => Line::from(String::from(text)),
+    }
+}
+
+impl<'a> StatefulWidget for SourceWidget<'a> {
+    type State = PmemTableState;
+
+    fn render(self, area: Rect, buf: &mut Buffer, state: &mut Self::State) {
+        let lines = self.source.lines();
+        let visible_lines = (area.height - 2) as usize; // Subtract 2 for the block borders
+        let scroll_offset = visible_lines / 5;
+
+        if state.focus_executing {
+            state.selected = *self.program_counter as u32;
+        }
+        state.selected = state
+            .selected
+            .min(self.source.instruction_count().saturating_sub(1) as u32);
+
+        // Keep the line of the selected instruction inside the viewport
+        if let Some(line) = self.source.line_of(state.selected as u16) {
+            if line < state.source_viewport_begin + scroll_offset {
+                state.source_viewport_begin = line.saturating_sub(scroll_offset);
+            } else if line + scroll_offset >= state.source_viewport_begin + visible_lines {
+                state.source_viewport_begin =
+                    (line + scroll_offset + 1).saturating_sub(visible_lines);
+            }
+        }
+        state.source_viewport_begin = state
+            .source_viewport_begin
+            .min(lines.len().saturating_sub(visible_lines));
+
+        let rows: Vec<Row> = lines
+            .iter()
+            .enumerate()
+            .skip(state.source_viewport_begin)
+            .take(visible_lines)
+            .map(|(index, text)| {
+                let address = self.source.address_on_line(index);
+
+                // Address of the instruction on the line, colored like in program memory
+                let address_cell = match address {
+                    Some(address) => Cell::from(format!("{:04x}", address))
+                        .fg(breakpoint_color(self.breakpoints, address)),
+                    None => Cell::from(""),
+                };
+
+                let mut row = Row::new(vec![
+                    address_cell,
+                    Cell::from(format!("{:>3}", index + 1)).fg(Color::Blue),
+                    Cell::from(highlight_line(text)),
+                ]);
+
+                if address == Some(*self.program_counter) {
+                    row = row.bg(Color::DarkGray);
+                }
+                if address == Some(state.selected as u16) {
+                    row = row.bold();
+                }
+                row
+            })
+            .collect();
+
+        let table = Table::new(rows)
+            .block(
+                Block::default()
+                    .title(" Source ")
+                    .title_alignment(Alignment::Center)
+                    .borders(Borders::ALL)
+                    .border_type(match state.is_focussed {
+                        true => BorderType::Thick,
+                        false => BorderType::Plain,
+                    }),
+            )
+            .column_spacing(1)
+            .widths(
+                [
+                    Constraint::Length(4),
+                    Constraint::Length(3),
+                    Constraint::Percentage(90),
+                ]
+                .as_ref(),
+            );
+
+        Widget::render(table, area, buf)
+    }
+}
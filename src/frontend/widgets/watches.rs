@@ -0,0 +1,56 @@
+use ratatui::prelude::{Alignment, Buffer, Constraint, Rect};
+use ratatui::style::{Color, Stylize};
+use ratatui::widgets::{Block, BorderType, Borders, Cell, Padding, Row, Table, Widget};
+
+use crate::backend::watch_list::WatchList;
+
+// Values of the watch expressions, the ones changed by the last step are highlighted
+pub struct WatchesWidget<'a> {
+    watch_list: &'a WatchList,
+}
+
+impl<'a> WatchesWidget<'a> {
+    pub fn new(watch_list: &'a WatchList) -> Self {
+        WatchesWidget { watch_list }
+    }
+}
+
+impl<'a> Widget for WatchesWidget<'a> {
+    fn render(self, area: Rect, buf: &mut Buffer) {
+        let rows = self
+            .watch_list
+            .values()
+            .enumerate()
+            .map(|(i, (expr, value, changed))| {
+                let value = match changed {
+                    true => Cell::from(value).fg(Color::LightRed).bold(),
+                    false => Cell::from(value),
+                };
+                Row::new(vec![
+                    Cell::from(format!("{}", i + 1)).fg(Color::DarkGray),
+                    Cell::from(expr.source.as_str()).blue(),
+                    value,
+                ])
+            });
+
+        let table = Table::new(rows)
+            .block(
+                Block::default()
+                    .title(" Watch ")
+                    .title_alignment(Alignment::Center)
+                    .borders(Borders::ALL)
+                    .border_type(BorderType::Plain)
+                    .padding(Padding::new(1, 1, 0, 0)),
+            )
+            .widths(
+                [
+                    Constraint::Length(2),
+                    Constraint::Percentage(45),
+                    Constraint::Percentage(55),
+                ]
+                .as_ref(),
+            );
+
+        Widget::render(table, area, buf)
+    }
+}
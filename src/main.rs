@@ -13,16 +13,73 @@ mod backend;
 mod frontend;
 mod util;
 
-use frontend::App;
+use frontend::{App, Headless};
 use std::env;
+use std::process;
+
+const USAGE: &str = "Usage: mpmp-emu [--headless [--dump-on-dbg]] [--script <commands>] [file]";
 
 fn main() {
-    let args: Vec<String> = env::args().collect();
+    let mut headless = false;
+    let mut dump_on_debug = false;
+    let mut script: Option<String> = None;
+    let mut file: Option<String> = None;
+
+    let mut args = env::args().skip(1);
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "--headless" => headless = true,
+            "--dump-on-dbg" => dump_on_debug = true,
+            "--script" => match args.next() {
+                Some(path) => script = Some(path),
+                None => {
+                    eprintln!("{}", USAGE);
+                    process::exit(2);
+                }
+            },
+            "-h" | "--help" => {
+                println!("{}", USAGE);
+                return;
+            }
+            _ if arg.starts_with("--") || file.is_some() => {
+                eprintln!("{}", USAGE);
+                process::exit(2);
+            }
+            _ => file = Some(arg),
+        }
+    }
+
+    if headless {
+        let mut emu = Headless::new();
+        emu.dump_on_debug = dump_on_debug;
+
+        if let Some(path) = file {
+            if !emu.try_load_program(path) {
+                process::exit(1);
+            }
+        }
+
+        // A script may load the program itself
+        match (script, emu.has_program()) {
+            (Some(script), _) => process::exit(emu.run_script(&script)),
+            (None, true) => process::exit(emu.run()),
+            (None, false) => {
+                eprintln!("{}", USAGE);
+                process::exit(2);
+            }
+        }
+    }
 
     let mut app = App::new();
 
-    if args.len() == 2 {
-        app.try_load_program(args[1].clone());
+    match file {
+        Some(path) => {
+            app.try_load_program(path);
+        }
+        None => app.load_last_program(),
+    }
+    if let Some(script) = script {
+        app.run_script(&script);
     }
 
     app.run();
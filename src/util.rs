@@ -13,6 +13,10 @@ impl Timer {
         }
     }
 
+    pub fn duration(&self) -> Duration {
+        self.target_duration
+    }
+
     pub fn set_duration(&mut self, target_duration: Duration) {
         self.target_duration = target_duration;
     }